@@ -1,4 +1,5 @@
-use std::sync::{Arc, OnceLock};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use trustfall::{
     FieldValue, Schema,
@@ -10,12 +11,84 @@ use trustfall::{
 };
 
 use super::vertex::Vertex;
+use crate::backend::Backend;
+use crate::error::Error;
+use crate::{BuildCache, Container, ContainerDetails, Image, Layer, Network, Volume};
 
 static SCHEMA: OnceLock<Schema> = OnceLock::new();
 
+/// Where the first error hit while running a query is kept, see [`Adapter::record_error`].
+type ErrorSlot = Arc<Mutex<Option<Error>>>;
+
+fn record(slot: &ErrorSlot, error: Error) {
+    let mut slot = slot.lock().unwrap();
+    if slot.is_none() {
+        *slot = Some(error);
+    }
+}
+
+/// Each image's layer digests keyed by its ID.
+pub(super) type LayerDigests = BTreeMap<String, Vec<String>>;
+
+/// Each image's layers with their sizes, which take a call or two per image to look up so
+/// they're only looked up for the images a query asks about. A layer shared between images is
+/// the same `Arc`.
+#[derive(Debug)]
+pub(super) struct Layers {
+    backend: Arc<dyn Backend>,
+    images: Mutex<HashMap<String, Arc<[Arc<Layer>]>>>,
+    shared: Mutex<HashMap<String, Arc<Layer>>>,
+    error: ErrorSlot,
+}
+
+impl Layers {
+    /// An image whose layers can't be looked up, or can't be matched up with their sizes, has
+    /// none and the error is recorded like any other the adapter hits.
+    pub(super) fn of(&self, image: &Image) -> Arc<[Arc<Layer>]> {
+        if let Some(layers) = self.images.lock().unwrap().get(&image.hash) {
+            return layers.clone();
+        }
+        let layers = self.backend.layers(image).unwrap_or_else(|e| {
+            record(&self.error, e);
+            vec![]
+        });
+        let layers: Arc<[Arc<Layer>]> = {
+            let mut shared = self.shared.lock().unwrap();
+            layers
+                .into_iter()
+                .map(|layer| {
+                    shared
+                        .entry(layer.digest.clone())
+                        .or_insert_with(|| Arc::new(layer))
+                        .clone()
+                })
+                .collect()
+        };
+        self.images
+            .lock()
+            .unwrap()
+            .insert(image.hash.clone(), layers.clone());
+        layers
+    }
+}
+
+/// What inspecting the containers found, keyed by container ID.
+pub(super) type Details = HashMap<String, ContainerDetails>;
+
 #[non_exhaustive]
 #[derive(Debug)]
-pub struct Adapter {}
+pub struct Adapter {
+    backend: Arc<dyn Backend>,
+    images: OnceLock<Arc<[Arc<Image>]>>,
+    containers: OnceLock<Arc<[Arc<Container>]>>,
+    container_details: OnceLock<Arc<Details>>,
+    layers: Arc<Layers>,
+    layer_digests: OnceLock<Arc<LayerDigests>>,
+    volumes: OnceLock<Arc<[Arc<Volume>]>>,
+    build_cache: OnceLock<Arc<[Arc<BuildCache>]>>,
+    networks: OnceLock<Arc<[Arc<Network>]>>,
+    error: ErrorSlot,
+}
 
 impl Adapter {
     pub const SCHEMA_TEXT: &'static str = include_str!("./schema.graphql");
@@ -24,8 +97,133 @@ impl Adapter {
         SCHEMA.get_or_init(|| Schema::parse(Self::SCHEMA_TEXT).expect("not a valid schema"))
     }
 
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        let error = ErrorSlot::default();
+        Self {
+            backend: backend.clone(),
+            images: OnceLock::new(),
+            containers: OnceLock::new(),
+            container_details: OnceLock::new(),
+            layers: Arc::new(Layers {
+                backend,
+                images: Mutex::default(),
+                shared: Mutex::default(),
+                error: error.clone(),
+            }),
+            layer_digests: OnceLock::new(),
+            volumes: OnceLock::new(),
+            build_cache: OnceLock::new(),
+            networks: OnceLock::new(),
+            error,
+        }
+    }
+
+    /// Trustfall has no way to fail a query from inside the adapter, so errors are stashed here
+    /// and the vertices affected resolve to nothing. Check [`Adapter::take_error`] once the
+    /// results have been collected.
+    pub(super) fn record_error(&self, error: Error) {
+        record(&self.error, error);
+    }
+
+    /// The first error hit while running a query, if any.
+    pub fn take_error(&self) -> Option<Error> {
+        self.error.lock().unwrap().take()
+    }
+
+    /// Images are only listed once per adapter so every edge sees a consistent view.
+    pub(super) fn images(&self) -> Arc<[Arc<Image>]> {
+        self.images
+            .get_or_init(|| {
+                let images = self.backend.images().unwrap_or_else(|e| {
+                    self.record_error(e);
+                    vec![]
+                });
+                images.into_iter().map(Arc::new).collect()
+            })
+            .clone()
+    }
+
+    pub(super) fn containers(&self) -> Arc<[Arc<Container>]> {
+        self.containers
+            .get_or_init(|| {
+                let containers = self.backend.containers().unwrap_or_else(|e| {
+                    self.record_error(e);
+                    vec![]
+                });
+                containers.into_iter().map(Arc::new).collect()
+            })
+            .clone()
+    }
+
+    /// Like [`Adapter::layers`], inspecting the containers is only done if a query needs it.
+    pub(super) fn container_details(&self) -> Arc<Details> {
+        self.container_details
+            .get_or_init(|| {
+                let containers = self.containers();
+                let ids = containers.iter().map(|c| c.id.as_str()).collect::<Vec<_>>();
+                let details = self.backend.container_details(&ids).unwrap_or_else(|e| {
+                    self.record_error(e);
+                    vec![]
+                });
+                Arc::new(details.into_iter().map(|d| (d.id.clone(), d)).collect())
+            })
+            .clone()
+    }
+
+    pub(super) fn volumes(&self) -> Arc<[Arc<Volume>]> {
+        self.volumes
+            .get_or_init(|| {
+                let volumes = self.backend.volumes().unwrap_or_else(|e| {
+                    self.record_error(e);
+                    vec![]
+                });
+                volumes.into_iter().map(Arc::new).collect()
+            })
+            .clone()
+    }
+
+    pub(super) fn build_cache(&self) -> Arc<[Arc<BuildCache>]> {
+        self.build_cache
+            .get_or_init(|| {
+                let records = self.backend.build_cache().unwrap_or_else(|e| {
+                    self.record_error(e);
+                    vec![]
+                });
+                records.into_iter().map(Arc::new).collect()
+            })
+            .clone()
+    }
+
+    pub(super) fn networks(&self) -> Arc<[Arc<Network>]> {
+        self.networks
+            .get_or_init(|| {
+                let networks = self.backend.networks().unwrap_or_else(|e| {
+                    self.record_error(e);
+                    vec![]
+                });
+                networks.into_iter().map(Arc::new).collect()
+            })
+            .clone()
+    }
+
+    pub(super) fn layers(&self) -> Arc<Layers> {
+        self.layers.clone()
+    }
+
+    /// Every image's layer digests, which backends can look up for all of them at once.
+    pub(super) fn layer_digests(&self) -> Arc<LayerDigests> {
+        self.layer_digests
+            .get_or_init(|| {
+                let images = self.images();
+                let images = images.iter().map(|i| i.as_ref()).collect::<Vec<_>>();
+                let digests = self.backend.layer_digests(&images).unwrap_or_else(|e| {
+                    self.record_error(e);
+                    vec![]
+                });
+                let ids = images.iter().map(|i| i.hash.clone());
+                Arc::new(ids.zip(digests).collect())
+            })
+            .clone()
     }
 }
 
@@ -35,11 +233,16 @@ impl<'a> trustfall::provider::Adapter<'a> for Adapter {
     fn resolve_starting_vertices(
         &self,
         edge_name: &Arc<str>,
-        parameters: &EdgeParameters,
+        _parameters: &EdgeParameters,
         resolve_info: &ResolveInfo,
     ) -> VertexIterator<'a, Self::Vertex> {
         match edge_name.as_ref() {
-            "Image" => super::entrypoints::image(resolve_info),
+            "Image" => super::entrypoints::image(self.images(), resolve_info),
+            "Container" => super::entrypoints::container(self.containers(), resolve_info),
+            "Volume" => super::entrypoints::volume(self.volumes(), resolve_info),
+            "BuildCache" => super::entrypoints::build_cache(self.build_cache(), resolve_info),
+            "Network" => super::entrypoints::network(self.networks(), resolve_info),
+            "Reference" => super::entrypoints::reference(self.images(), resolve_info),
             _ => {
                 unreachable!(
                     "attempted to resolve starting vertices for unexpected edge name: {edge_name}"
@@ -60,6 +263,38 @@ impl<'a> trustfall::provider::Adapter<'a> for Adapter {
         }
         match type_name.as_ref() {
             "Image" => super::properties::resolve_image_property(
+                contexts,
+                property_name.as_ref(),
+                self,
+                resolve_info,
+            ),
+            "Container" => super::properties::resolve_container_property(
+                contexts,
+                property_name.as_ref(),
+                self,
+                resolve_info,
+            ),
+            "Layer" => super::properties::resolve_layer_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "Volume" => super::properties::resolve_volume_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "BuildCache" => super::properties::resolve_build_cache_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "Network" => super::properties::resolve_network_property(
+                contexts,
+                property_name.as_ref(),
+                resolve_info,
+            ),
+            "Reference" => super::properties::resolve_reference_property(
                 contexts,
                 property_name.as_ref(),
                 resolve_info,
@@ -85,6 +320,44 @@ impl<'a> trustfall::provider::Adapter<'a> for Adapter {
                 contexts,
                 edge_name.as_ref(),
                 parameters,
+                self,
+                resolve_info,
+            ),
+            "Container" => super::edges::resolve_container_edge(
+                contexts,
+                edge_name.as_ref(),
+                parameters,
+                self,
+                resolve_info,
+            ),
+            "Layer" => {
+                super::edges::resolve_layer_edge(contexts, edge_name.as_ref(), self, resolve_info)
+            }
+            "Volume" => super::edges::resolve_volume_edge(
+                contexts,
+                edge_name.as_ref(),
+                parameters,
+                self,
+                resolve_info,
+            ),
+            "BuildCache" => super::edges::resolve_build_cache_edge(
+                contexts,
+                edge_name.as_ref(),
+                parameters,
+                self,
+                resolve_info,
+            ),
+            "Network" => super::edges::resolve_network_edge(
+                contexts,
+                edge_name.as_ref(),
+                parameters,
+                self,
+                resolve_info,
+            ),
+            "Reference" => super::edges::resolve_reference_edge(
+                contexts,
+                edge_name.as_ref(),
+                self,
                 resolve_info,
             ),
             _ => {
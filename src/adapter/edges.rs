@@ -1,4 +1,9 @@
+use super::Adapter;
+use super::adapter_impl::Details;
 use super::vertex::Vertex;
+use crate::Container;
+use crate::error::Error;
+use jiff::Timestamp;
 use trustfall::provider::{
     AsVertex, ContextIterator, ContextOutcomeIterator, EdgeParameters, ResolveEdgeInfo,
     VertexIterator,
@@ -8,39 +13,239 @@ pub(super) fn resolve_image_edge<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     edge_name: &str,
     parameters: &EdgeParameters,
+    adapter: &Adapter,
     resolve_info: &ResolveEdgeInfo,
 ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let timestamp = || {
+        parameters
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .expect("'timestamp' is a required String! parameter")
+    };
+    match edge_name {
+        "created_after" => match parse_timestamp(timestamp(), adapter) {
+            Some(ts) => image::matching(contexts, move |i| i.created_at > ts, resolve_info),
+            None => no_neighbors(contexts),
+        },
+        "created_before" => match parse_timestamp(timestamp(), adapter) {
+            Some(ts) => image::matching(contexts, move |i| i.created_at < ts, resolve_info),
+            None => no_neighbors(contexts),
+        },
+        "used_by_containers" => {
+            image::used_by_containers(contexts, adapter.containers(), resolve_info)
+        }
+        "parent" => image::parent(contexts, adapter.images(), resolve_info),
+        "children" => image::children(contexts, adapter.images(), resolve_info),
+        "layers" => image::layers(contexts, adapter.layers(), resolve_info),
+        "references" => image::references(contexts, resolve_info),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Image'")
+        }
+    }
+}
+
+fn parse_timestamp(timestamp: &str, adapter: &Adapter) -> Option<Timestamp> {
+    timestamp
+        .parse()
+        .map_err(|source| {
+            adapter.record_error(Error::InvalidTimestamp {
+                value: timestamp.to_string(),
+                source,
+            })
+        })
+        .ok()
+}
+
+fn no_neighbors<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    Box::new(contexts.map(|ctx| {
+        let neighbors: VertexIterator<'a, Vertex> = Box::new(std::iter::empty());
+        (ctx, neighbors)
+    }))
+}
+
+pub(super) fn resolve_container_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    parameters: &EdgeParameters,
+    adapter: &Adapter,
+    resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    if edge_name == "image" {
+        return container::image(contexts, adapter.images(), resolve_info);
+    }
+    let timestamp = parameters
+        .get("timestamp")
+        .and_then(|t| t.as_str())
+        .expect("'timestamp' is a required String! parameter");
+    let Some(ts) = parse_timestamp(timestamp, adapter) else {
+        return no_neighbors(contexts);
+    };
+    let finished_at =
+        |details: &Details, c: &Container| details.get(&c.id).and_then(|d| d.finished_at);
+    match edge_name {
+        "created_after" => container::matching(contexts, move |c| c.created_at > ts, resolve_info),
+        "created_before" => container::matching(contexts, move |c| c.created_at < ts, resolve_info),
+        "exited_after" => {
+            let details = adapter.container_details();
+            container::matching(
+                contexts,
+                move |c| finished_at(&details, c).is_some_and(|f| f > ts),
+                resolve_info,
+            )
+        }
+        "exited_before" => {
+            let details = adapter.container_details();
+            container::matching(
+                contexts,
+                move |c| finished_at(&details, c).is_some_and(|f| f < ts),
+                resolve_info,
+            )
+        }
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Container'")
+        }
+    }
+}
+
+pub(super) fn resolve_layer_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    adapter: &Adapter,
+    resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "images" => layer::images(
+            contexts,
+            adapter.images(),
+            adapter.layer_digests(),
+            resolve_info,
+        ),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Layer'")
+        }
+    }
+}
+
+pub(super) fn resolve_volume_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    parameters: &EdgeParameters,
+    adapter: &Adapter,
+    resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let timestamp = || {
+        parameters
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .expect("'timestamp' is a required String! parameter")
+    };
+    match edge_name {
+        // Volumes without a creation time match neither edge
+        "created_after" => match parse_timestamp(timestamp(), adapter) {
+            Some(ts) => volume::matching(
+                contexts,
+                move |v| v.created_at.is_some_and(|created| created > ts),
+                resolve_info,
+            ),
+            None => no_neighbors(contexts),
+        },
+        "created_before" => match parse_timestamp(timestamp(), adapter) {
+            Some(ts) => volume::matching(
+                contexts,
+                move |v| v.created_at.is_some_and(|created| created < ts),
+                resolve_info,
+            ),
+            None => no_neighbors(contexts),
+        },
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Volume'")
+        }
+    }
+}
+
+pub(super) fn resolve_build_cache_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    parameters: &EdgeParameters,
+    adapter: &Adapter,
+    resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let timestamp = parameters
+        .get("timestamp")
+        .and_then(|t| t.as_str())
+        .expect("'timestamp' is a required String! parameter");
+    let Some(ts) = parse_timestamp(timestamp, adapter) else {
+        return no_neighbors(contexts);
+    };
     match edge_name {
         "created_after" => {
-            let timestamp: &str = parameters
-                .get("timestamp")
-                .expect(
-                    "failed to find parameter 'timestamp' for edge 'created_after' on type 'Image'",
-                )
-                .as_str()
-                .expect("unexpected null or other incorrect datatype for Trustfall type 'String!'");
-            image::created_after(contexts, timestamp, resolve_info)
+            build_cache::matching(contexts, move |c| c.created_at > ts, resolve_info)
         }
         "created_before" => {
-            let timestamp: &str = parameters
-                .get("timestamp")
-                .expect(
-                    "failed to find parameter 'timestamp' for edge 'created_before' on type 'Image'",
-                )
-                .as_str()
-                .expect(
-                    "unexpected null or other incorrect datatype for Trustfall type 'String!'",
-                );
-            image::created_before(contexts, timestamp, resolve_info)
+            build_cache::matching(contexts, move |c| c.created_at < ts, resolve_info)
+        }
+        "last_used_after" => {
+            build_cache::matching(contexts, move |c| c.last_active() > ts, resolve_info)
+        }
+        "last_used_before" => {
+            build_cache::matching(contexts, move |c| c.last_active() < ts, resolve_info)
         }
         _ => {
-            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Image'")
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'BuildCache'")
+        }
+    }
+}
+
+pub(super) fn resolve_network_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    parameters: &EdgeParameters,
+    adapter: &Adapter,
+    resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    let timestamp = || {
+        parameters
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .expect("'timestamp' is a required String! parameter")
+    };
+    match edge_name {
+        "containers" => network::containers(contexts, adapter.containers(), resolve_info),
+        "created_after" => match parse_timestamp(timestamp(), adapter) {
+            Some(ts) => network::matching(contexts, move |n| n.created_at > ts, resolve_info),
+            None => no_neighbors(contexts),
+        },
+        "created_before" => match parse_timestamp(timestamp(), adapter) {
+            Some(ts) => network::matching(contexts, move |n| n.created_at < ts, resolve_info),
+            None => no_neighbors(contexts),
+        },
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Network'")
+        }
+    }
+}
+
+pub(super) fn resolve_reference_edge<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    edge_name: &str,
+    adapter: &Adapter,
+    resolve_info: &ResolveEdgeInfo,
+) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+    match edge_name {
+        "image" => reference::image(contexts, adapter.images(), resolve_info),
+        _ => {
+            unreachable!("attempted to resolve unexpected edge '{edge_name}' on type 'Reference'")
         }
     }
 }
 
 mod image {
-    use jiff::Timestamp;
+    use super::super::adapter_impl::Layers;
+    use crate::containers::same_id;
+    use crate::{Container, Image, ImageReference};
+    use std::sync::Arc;
     use trustfall::provider::{
         AsVertex, ContextIterator, ContextOutcomeIterator, ResolveEdgeInfo, VertexIterator,
         resolve_neighbors_with,
@@ -48,19 +253,17 @@ mod image {
 
     use super::super::vertex::Vertex;
 
-    pub(super) fn created_after<'a, V: AsVertex<Vertex> + 'a>(
+    /// The image itself if it passes the test.
+    pub(super) fn matching<'a, V: AsVertex<Vertex> + 'a>(
         contexts: ContextIterator<'a, V>,
-        timestamp: &str,
+        test: impl Fn(&Image) -> bool + 'a,
         _resolve_info: &ResolveEdgeInfo,
     ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
-        let ts = timestamp.parse::<Timestamp>().unwrap();
-
         resolve_neighbors_with(contexts, move |vertex| {
             let image = vertex
                 .as_image()
                 .expect("conversion failed, vertex was not a Image");
-
-            if image.created_at > ts {
+            if test(image) {
                 Box::new(std::iter::once(vertex.clone()))
             } else {
                 Box::new(std::iter::empty())
@@ -68,17 +271,200 @@ mod image {
         })
     }
 
-    pub(super) fn created_before<'a, V: AsVertex<Vertex> + 'a>(
+    pub(super) fn used_by_containers<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        containers: Arc<[Arc<Container>]>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let image = vertex
+                .as_image()
+                .expect("conversion failed, vertex was not a Image");
+            let used_by = containers
+                .iter()
+                .filter(|c| c.uses_image(image))
+                .map(|c| Vertex::Container(c.clone()))
+                .collect::<Vec<_>>();
+            Box::new(used_by.into_iter())
+        })
+    }
+
+    fn is_parent(parent_id: &str, image: &Image) -> bool {
+        parent_id == image.hash || same_id(parent_id, &image.hash)
+    }
+
+    pub(super) fn parent<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        images: Arc<[Arc<Image>]>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let image = vertex
+                .as_image()
+                .expect("conversion failed, vertex was not a Image");
+            let parent = image
+                .parent
+                .as_ref()
+                .and_then(|parent| images.iter().find(|i| is_parent(parent, i)))
+                .map(|i| Vertex::Image(i.clone()));
+            Box::new(parent.into_iter())
+        })
+    }
+
+    pub(super) fn children<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        images: Arc<[Arc<Image>]>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let image = vertex
+                .as_image()
+                .expect("conversion failed, vertex was not a Image");
+            let children = images
+                .iter()
+                .filter(|i| i.parent.as_ref().is_some_and(|p| is_parent(p, image)))
+                .map(|i| Vertex::Image(i.clone()))
+                .collect::<Vec<_>>();
+            Box::new(children.into_iter())
+        })
+    }
+
+    pub(super) fn layers<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        layers: Arc<Layers>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let image = vertex
+                .as_image()
+                .expect("conversion failed, vertex was not a Image");
+            let image_layers = layers
+                .of(image)
+                .iter()
+                .map(|l| Vertex::Layer(l.clone()))
+                .collect::<Vec<_>>();
+            Box::new(image_layers.into_iter())
+        })
+    }
+
+    pub(super) fn references<'a, V: AsVertex<Vertex> + 'a>(
         contexts: ContextIterator<'a, V>,
-        timestamp: &str,
         _resolve_info: &ResolveEdgeInfo,
     ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
-        let ts = timestamp.parse::<Timestamp>().unwrap();
         resolve_neighbors_with(contexts, move |vertex| {
             let image = vertex
                 .as_image()
                 .expect("conversion failed, vertex was not a Image");
-            if image.created_at < ts {
+            let references = ImageReference::all([image.as_ref()])
+                .into_iter()
+                .map(|r| Vertex::Reference(Arc::new(r)))
+                .collect::<Vec<_>>();
+            Box::new(references.into_iter())
+        })
+    }
+}
+
+mod container {
+    use crate::{Container, Image};
+    use std::sync::Arc;
+    use trustfall::provider::{
+        AsVertex, ContextIterator, ContextOutcomeIterator, ResolveEdgeInfo, VertexIterator,
+        resolve_neighbors_with,
+    };
+
+    use super::super::vertex::Vertex;
+
+    pub(super) fn image<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        images: Arc<[Arc<Image>]>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let container = vertex
+                .as_container()
+                .expect("conversion failed, vertex was not a Container");
+            let image = images
+                .iter()
+                .find(|img| container.uses_image(img))
+                .map(|img| Vertex::Image(img.clone()));
+            Box::new(image.into_iter())
+        })
+    }
+
+    /// The container itself if it passes the test.
+    pub(super) fn matching<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        test: impl Fn(&Container) -> bool + 'a,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let container = vertex
+                .as_container()
+                .expect("conversion failed, vertex was not a Container");
+            if test(container) {
+                Box::new(std::iter::once(vertex.clone()))
+            } else {
+                Box::new(std::iter::empty())
+            }
+        })
+    }
+}
+
+mod layer {
+    use super::super::adapter_impl::LayerDigests;
+    use crate::Image;
+    use std::sync::Arc;
+    use trustfall::provider::{
+        AsVertex, ContextIterator, ContextOutcomeIterator, ResolveEdgeInfo, VertexIterator,
+        resolve_neighbors_with,
+    };
+
+    use super::super::vertex::Vertex;
+
+    pub(super) fn images<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        images: Arc<[Arc<Image>]>,
+        digests: Arc<LayerDigests>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let layer = vertex
+                .as_layer()
+                .expect("conversion failed, vertex was not a Layer");
+            let using = images
+                .iter()
+                .filter(|img| {
+                    digests
+                        .get(&img.hash)
+                        .is_some_and(|d| d.contains(&layer.digest))
+                })
+                .map(|img| Vertex::Image(img.clone()))
+                .collect::<Vec<_>>();
+            Box::new(using.into_iter())
+        })
+    }
+}
+
+mod volume {
+    use crate::Volume;
+    use trustfall::provider::{
+        AsVertex, ContextIterator, ContextOutcomeIterator, ResolveEdgeInfo, VertexIterator,
+        resolve_neighbors_with,
+    };
+
+    use super::super::vertex::Vertex;
+
+    /// The volume itself if it passes the test.
+    pub(super) fn matching<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        test: impl Fn(&Volume) -> bool + 'a,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let volume = vertex
+                .as_volume()
+                .expect("conversion failed, vertex was not a Volume");
+            if test(volume) {
                 Box::new(std::iter::once(vertex.clone()))
             } else {
                 Box::new(std::iter::empty())
@@ -86,3 +472,106 @@ mod image {
         })
     }
 }
+
+mod build_cache {
+    use crate::BuildCache;
+    use trustfall::provider::{
+        AsVertex, ContextIterator, ContextOutcomeIterator, ResolveEdgeInfo, VertexIterator,
+        resolve_neighbors_with,
+    };
+
+    use super::super::vertex::Vertex;
+
+    /// The record itself if it passes the test, every edge on a record is a filter on it.
+    pub(super) fn matching<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        test: impl Fn(&BuildCache) -> bool + 'a,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let record = vertex
+                .as_build_cache()
+                .expect("conversion failed, vertex was not a BuildCache");
+            if test(record) {
+                Box::new(std::iter::once(vertex.clone()))
+            } else {
+                Box::new(std::iter::empty())
+            }
+        })
+    }
+}
+
+mod network {
+    use crate::{Container, Network};
+    use std::sync::Arc;
+    use trustfall::provider::{
+        AsVertex, ContextIterator, ContextOutcomeIterator, ResolveEdgeInfo, VertexIterator,
+        resolve_neighbors_with,
+    };
+
+    use super::super::vertex::Vertex;
+
+    pub(super) fn containers<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        containers: Arc<[Arc<Container>]>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let network = vertex
+                .as_network()
+                .expect("conversion failed, vertex was not a Network");
+            let attached = containers
+                .iter()
+                .filter(|c| network.is_attached(c))
+                .map(|c| Vertex::Container(c.clone()))
+                .collect::<Vec<_>>();
+            Box::new(attached.into_iter())
+        })
+    }
+
+    /// The network itself if it passes the test.
+    pub(super) fn matching<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        test: impl Fn(&Network) -> bool + 'a,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let network = vertex
+                .as_network()
+                .expect("conversion failed, vertex was not a Network");
+            if test(network) {
+                Box::new(std::iter::once(vertex.clone()))
+            } else {
+                Box::new(std::iter::empty())
+            }
+        })
+    }
+}
+
+mod reference {
+    use crate::Image;
+    use std::sync::Arc;
+    use trustfall::provider::{
+        AsVertex, ContextIterator, ContextOutcomeIterator, ResolveEdgeInfo, VertexIterator,
+        resolve_neighbors_with,
+    };
+
+    use super::super::vertex::Vertex;
+
+    pub(super) fn image<'a, V: AsVertex<Vertex> + 'a>(
+        contexts: ContextIterator<'a, V>,
+        images: Arc<[Arc<Image>]>,
+        _resolve_info: &ResolveEdgeInfo,
+    ) -> ContextOutcomeIterator<'a, V, VertexIterator<'a, Vertex>> {
+        resolve_neighbors_with(contexts, move |vertex| {
+            let reference = vertex
+                .as_reference()
+                .expect("conversion failed, vertex was not a Reference");
+            let image = images
+                .iter()
+                .find(|img| img.hash == reference.image)
+                .map(|img| Vertex::Image(img.clone()));
+            Box::new(image.into_iter())
+        })
+    }
+}
@@ -1,37 +1,52 @@
-use crate::ImageOutput;
-use std::process::Command;
+use crate::{BuildCache, Container, Image, ImageReference, Network, Volume};
 use std::sync::Arc;
 use trustfall::provider::{ResolveInfo, VertexIterator};
 
 use super::vertex::Vertex;
 
-pub(super) fn image<'a>(_resolve_info: &ResolveInfo) -> VertexIterator<'a, Vertex> {
-    let version = Command::new("docker")
-        .args(["--version"])
-        .output()
-        .expect("couldn't get docker version");
-    let version = String::from_utf8_lossy(&version.stdout);
-    let is_podman = version.contains("podman");
+pub(super) fn image<'a>(
+    images: Arc<[Arc<Image>]>,
+    _resolve_info: &ResolveInfo,
+) -> VertexIterator<'a, Vertex> {
+    Box::new((0..images.len()).map(move |i| Vertex::Image(images[i].clone())))
+}
+
+pub(super) fn container<'a>(
+    containers: Arc<[Arc<Container>]>,
+    _resolve_info: &ResolveInfo,
+) -> VertexIterator<'a, Vertex> {
+    Box::new((0..containers.len()).map(move |i| Vertex::Container(containers[i].clone())))
+}
 
-    let images = Command::new("docker")
-        .args(["image", "ls", "--format", "json"])
-        .output()
-        .expect("failed to run docker");
+pub(super) fn volume<'a>(
+    volumes: Arc<[Arc<Volume>]>,
+    _resolve_info: &ResolveInfo,
+) -> VertexIterator<'a, Vertex> {
+    Box::new((0..volumes.len()).map(move |i| Vertex::Volume(volumes[i].clone())))
+}
 
-    let images: Vec<ImageOutput> = if is_podman {
-        serde_json::from_slice(&images.stdout).expect("couldn't deserialize the json output")
-    } else {
-        let s = String::from_utf8_lossy(&images.stdout);
-        let mut v = vec![];
-        for line in s.lines() {
-            v.push(serde_json::from_str(line).expect("couldn't deserialize the json output"));
-        }
-        v
-    };
+pub(super) fn build_cache<'a>(
+    records: Arc<[Arc<BuildCache>]>,
+    _resolve_info: &ResolveInfo,
+) -> VertexIterator<'a, Vertex> {
+    Box::new((0..records.len()).map(move |i| Vertex::BuildCache(records[i].clone())))
+}
+
+pub(super) fn network<'a>(
+    networks: Arc<[Arc<Network>]>,
+    _resolve_info: &ResolveInfo,
+) -> VertexIterator<'a, Vertex> {
+    Box::new((0..networks.len()).map(move |i| Vertex::Network(networks[i].clone())))
+}
 
+pub(super) fn reference<'a>(
+    images: Arc<[Arc<Image>]>,
+    _resolve_info: &ResolveInfo,
+) -> VertexIterator<'a, Vertex> {
+    let references = ImageReference::all(images.iter().map(|img| img.as_ref()));
     Box::new(
-        images
+        references
             .into_iter()
-            .map(|x| Vertex::Image(Arc::new(x.into()))),
+            .map(|r| Vertex::Reference(Arc::new(r))),
     )
 }
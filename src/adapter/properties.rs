@@ -1,61 +1,125 @@
+use super::Adapter;
 use super::vertex::Vertex;
 use trustfall::{
     FieldValue,
-    provider::{AsVertex, ContextIterator, ContextOutcomeIterator, DataContext, ResolveInfo},
+    provider::{
+        AsVertex, ContextIterator, ContextOutcomeIterator, DataContext, ResolveInfo,
+        resolve_property_with,
+    },
 };
 
 pub(super) fn resolve_image_property<'a, V: AsVertex<Vertex> + 'a>(
     contexts: ContextIterator<'a, V>,
     property_name: &str,
+    adapter: &Adapter,
     _resolve_info: &ResolveInfo,
 ) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    // The rank depends on the other images so it can't be a plain function of the vertex
+    if property_name == "rank_in_repo" {
+        let images = adapter.images();
+        return resolve_property_with(contexts, move |vertex| {
+            let image = vertex
+                .as_image()
+                .expect("conversion failed, vertex was not a Image");
+            image
+                .rank_in_repository(images.iter().map(|img| img.as_ref()))
+                .into()
+        });
+    }
+    if property_name == "layer_digests" {
+        let digests = adapter.layer_digests();
+        return resolve_property_with(contexts, move |vertex| {
+            let image = vertex
+                .as_image()
+                .expect("conversion failed, vertex was not a Image");
+            digests
+                .get(&image.hash)
+                .map(|d| d.as_slice())
+                .unwrap_or_default()
+                .into()
+        });
+    }
     let func = match property_name {
+        "id" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.hash.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
         "created" => |v: DataContext<V>| match v.active_vertex() {
             Some(Vertex::Image(img)) => (v.clone(), img.created_at.to_string().into()),
-            None => (v, FieldValue::Null),
+            _ => (v, FieldValue::Null),
+        },
+        "registry" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.reference().map(|r| r.registry).into()),
+            _ => (v, FieldValue::Null),
+        },
+        "namespace" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.reference().map(|r| r.namespace).into()),
+            _ => (v, FieldValue::Null),
         },
         "repo" => |v: DataContext<V>| match v.active_vertex() {
-            Some(Vertex::Image(img)) => {
-                let value = if img.repository.is_empty() {
-                    FieldValue::Null
-                } else {
-                    img.repository.as_str().into()
-                };
-                (v.clone(), value)
-            }
-            None => (v, FieldValue::Null),
+            Some(Vertex::Image(img)) => (v.clone(), img.reference().map(|r| r.repository).into()),
+            _ => (v, FieldValue::Null),
         },
         "tag" => |v: DataContext<V>| match v.active_vertex() {
             Some(Vertex::Image(img)) => {
-                let value = if img.tag.as_str().is_empty() {
+                let value = if img.tag.is_empty() {
                     FieldValue::Null
                 } else {
                     img.tag.as_str().into()
                 };
                 (v.clone(), value)
             }
-            None => (v, FieldValue::Null),
+            _ => (v, FieldValue::Null),
         },
         "name" => |v: DataContext<V>| match v.active_vertex() {
             Some(Vertex::Image(img)) => {
                 let value = if img.repository.is_empty() {
                     FieldValue::Null
+                } else if img.tag.is_empty() {
+                    img.repository.as_str().into()
                 } else {
-                    if img.tag.is_empty() {
-                        img.repository.as_str().into()
-                    } else {
-                        let name = format!("{}:{}", img.repository, img.tag);
-                        name.into()
-                    }
+                    let name = format!("{}:{}", img.repository, img.tag);
+                    name.into()
                 };
                 (v.clone(), value)
             }
-            None => (v, FieldValue::Null),
+            _ => (v, FieldValue::Null),
         },
 
         "size" => |v: DataContext<V>| match v.active_vertex() {
             Some(Vertex::Image(img)) => (v.clone(), FieldValue::Uint64(img.size as u64)),
-            None => (v, FieldValue::Null),
+            _ => (v, FieldValue::Null),
+        },
+        "shared_size" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.shared_size.map(|s| s as u64).into()),
+            _ => (v, FieldValue::Null),
+        },
+        "unique_size" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.unique_size().map(|s| s as u64).into()),
+            _ => (v, FieldValue::Null),
+        },
+        "digest" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.digest.as_deref().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "repo_digests" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.repo_digests.as_slice().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "labels" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => {
+                let labels = img
+                    .labels
+                    .iter()
+                    .map(|(k, val)| format!("{k}={val}"))
+                    .collect::<Vec<_>>();
+                (v.clone(), labels.into())
+            }
+            _ => (v, FieldValue::Null),
+        },
+        "dangling" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Image(img)) => (v.clone(), img.is_dangling().into()),
+            _ => (v, FieldValue::Null),
         },
         _ => {
             unreachable!("attempted to read unexpected property '{property_name}' on type 'Image'")
@@ -63,3 +127,277 @@ pub(super) fn resolve_image_property<'a, V: AsVertex<Vertex> + 'a>(
     };
     Box::new(contexts.map(func))
 }
+
+pub(super) fn resolve_container_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    adapter: &Adapter,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    // These come from inspecting the container rather than the vertex itself
+    if let "exit_code" | "finished" | "size" = property_name {
+        let details = adapter.container_details();
+        let property_name = property_name.to_string();
+        return resolve_property_with(contexts, move |vertex| {
+            let container = vertex
+                .as_container()
+                .expect("conversion failed, vertex was not a Container");
+            let Some(d) = details.get(&container.id) else {
+                return FieldValue::Null;
+            };
+            match property_name.as_str() {
+                "exit_code" => d.exit_code.into(),
+                "finished" => d.finished_at.map(|t| t.to_string()).into(),
+                _ => d.size.into(),
+            }
+        });
+    }
+    let func = match property_name {
+        "id" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Container(c)) => (v.clone(), c.id.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "name" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Container(c)) => (v.clone(), c.name.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "state" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Container(c)) => (v.clone(), c.state.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "status" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Container(c)) => (v.clone(), c.status.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "created" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Container(c)) => (v.clone(), c.created_at.to_string().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "image_name" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Container(c)) => (v.clone(), c.image.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'Container'"
+            )
+        }
+    };
+    Box::new(contexts.map(func))
+}
+
+pub(super) fn resolve_layer_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let func = match property_name {
+        "digest" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Layer(l)) => (v.clone(), l.digest.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "size" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Layer(l)) => (v.clone(), FieldValue::Uint64(l.size)),
+            _ => (v, FieldValue::Null),
+        },
+        _ => {
+            unreachable!("attempted to read unexpected property '{property_name}' on type 'Layer'")
+        }
+    };
+    Box::new(contexts.map(func))
+}
+
+pub(super) fn resolve_volume_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let func = match property_name {
+        "name" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Volume(vol)) => (v.clone(), vol.name.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "driver" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Volume(vol)) => (v.clone(), vol.driver.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "mountpoint" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Volume(vol)) => (v.clone(), vol.mountpoint.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "created" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Volume(vol)) => (v.clone(), vol.created_at.map(|t| t.to_string()).into()),
+            _ => (v, FieldValue::Null),
+        },
+        "size" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Volume(vol)) => (v.clone(), vol.size.into()),
+            _ => (v, FieldValue::Null),
+        },
+        "in_use" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Volume(vol)) => (v.clone(), vol.in_use.into()),
+            _ => (v, FieldValue::Null),
+        },
+        "labels" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Volume(vol)) => {
+                let labels = vol
+                    .labels
+                    .iter()
+                    .map(|(k, val)| format!("{k}={val}"))
+                    .collect::<Vec<_>>();
+                (v.clone(), labels.into())
+            }
+            _ => (v, FieldValue::Null),
+        },
+        _ => {
+            unreachable!("attempted to read unexpected property '{property_name}' on type 'Volume'")
+        }
+    };
+    Box::new(contexts.map(func))
+}
+
+pub(super) fn resolve_build_cache_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let func = match property_name {
+        "id" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), c.id.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "type" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), c.cache_type.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "size" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), FieldValue::Uint64(c.size)),
+            _ => (v, FieldValue::Null),
+        },
+        "shared" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), c.shared.into()),
+            _ => (v, FieldValue::Null),
+        },
+        "in_use" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), c.in_use.into()),
+            _ => (v, FieldValue::Null),
+        },
+        "created" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), c.created_at.to_string().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "last_used" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => {
+                (v.clone(), c.last_used_at.map(|t| t.to_string()).into())
+            }
+            _ => (v, FieldValue::Null),
+        },
+        "usage_count" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), FieldValue::Uint64(c.usage_count)),
+            _ => (v, FieldValue::Null),
+        },
+        "description" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::BuildCache(c)) => (v.clone(), c.description.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'BuildCache'"
+            )
+        }
+    };
+    Box::new(contexts.map(func))
+}
+
+pub(super) fn resolve_network_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let func = match property_name {
+        "id" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => (v.clone(), n.id.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "name" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => (v.clone(), n.name.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "driver" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => (v.clone(), n.driver.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "scope" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => (v.clone(), n.scope.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "created" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => (v.clone(), n.created_at.to_string().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "labels" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => {
+                let labels = n
+                    .labels
+                    .iter()
+                    .map(|(k, val)| format!("{k}={val}"))
+                    .collect::<Vec<_>>();
+                (v.clone(), labels.into())
+            }
+            _ => (v, FieldValue::Null),
+        },
+        "internal" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => (v.clone(), n.internal.into()),
+            _ => (v, FieldValue::Null),
+        },
+        "predefined" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Network(n)) => (v.clone(), n.is_predefined().into()),
+            _ => (v, FieldValue::Null),
+        },
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'Network'"
+            )
+        }
+    };
+    Box::new(contexts.map(func))
+}
+
+pub(super) fn resolve_reference_property<'a, V: AsVertex<Vertex> + 'a>(
+    contexts: ContextIterator<'a, V>,
+    property_name: &str,
+    _resolve_info: &ResolveInfo,
+) -> ContextOutcomeIterator<'a, V, FieldValue> {
+    let func = match property_name {
+        "name" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Reference(r)) => (v.clone(), r.name.as_str().into()),
+            _ => (v, FieldValue::Null),
+        },
+        "registry" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Reference(r)) => (v.clone(), r.parse().map(|r| r.registry).into()),
+            _ => (v, FieldValue::Null),
+        },
+        "namespace" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Reference(r)) => (v.clone(), r.parse().map(|r| r.namespace).into()),
+            _ => (v, FieldValue::Null),
+        },
+        "repo" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Reference(r)) => (v.clone(), r.parse().map(|r| r.repository).into()),
+            _ => (v, FieldValue::Null),
+        },
+        "tag" => |v: DataContext<V>| match v.active_vertex() {
+            Some(Vertex::Reference(r)) => {
+                let tag = r
+                    .parse()
+                    .and_then(|r| r.tag_or_latest().map(str::to_string));
+                (v.clone(), tag.into())
+            }
+            _ => (v, FieldValue::Null),
+        },
+        _ => {
+            unreachable!(
+                "attempted to read unexpected property '{property_name}' on type 'Reference'"
+            )
+        }
+    };
+    Box::new(contexts.map(func))
+}
@@ -1,10 +1,12 @@
 use trustfall::provider::check_adapter_invariants;
 
 use super::Adapter;
+use crate::backend::Fixture;
+use std::sync::Arc;
 
 #[test]
 fn adapter_satisfies_trustfall_invariants() {
-    let adapter = Adapter::new();
+    let adapter = Adapter::new(Arc::new(Fixture::default()));
     let schema = Adapter::schema();
     check_adapter_invariants(schema, adapter);
 }
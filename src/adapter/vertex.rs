@@ -4,4 +4,10 @@ use std::sync::Arc;
 #[derive(Debug, Clone, trustfall::provider::TrustfallEnumVertex)]
 pub enum Vertex {
     Image(Arc<crate::Image>),
+    Container(Arc<crate::Container>),
+    Layer(Arc<crate::Layer>),
+    Volume(Arc<crate::Volume>),
+    BuildCache(Arc<crate::BuildCache>),
+    Network(Arc<crate::Network>),
+    Reference(Arc<crate::ImageReference>),
 }
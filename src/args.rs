@@ -1,48 +1,178 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use human_size::Size;
-use jiff::Timestamp;
+use jiff::{Span, Timestamp, Zoned};
+use std::path::PathBuf;
 use std::str::FromStr;
+use trustfall::{FieldValue, TransparentValue};
 
 /// CLI tool with ls, rm, and size commands.
 #[derive(Parser, Debug)]
 #[command(name = "cli-tool", version, about = "CLI for managing files", long_about = None)]
 pub struct Cli {
+    /// Which container runtime to talk to
+    #[arg(long, global = true, value_enum, default_value_t = Runtime::Auto)]
+    pub runtime: Runtime,
     #[command(subcommand)]
     pub command: Commands,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    /// Use the Engine API if its socket is reachable, otherwise whichever CLI is installed
+    Auto,
+    /// Run the `docker` CLI
+    Docker,
+    /// Run the `podman` CLI
+    Podman,
+    /// Talk to the Docker Engine API over its unix socket
+    Engine,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// List files
     Ls(FilterOptions),
     /// Remove files
     Rm(FilterOptions),
+    /// Remove the names of images the filters match, an image is only deleted once it has none
+    Untag(FilterOptions),
     /// Show total size
     Size(FilterOptions),
     /// Print the trustfall query
     Print(FilterOptions),
+    /// Run a trustfall query against the schema
+    Query(QueryOptions),
+    /// Run the rules in a cleanup policy file
+    Apply(ApplyOptions),
 }
 
 impl Commands {
-    pub fn filter(&self) -> &FilterOptions {
+    pub fn filter(&self) -> Option<&FilterOptions> {
         match self {
-            Self::Ls(f) => f,
-            Self::Rm(f) => f,
-            Self::Size(f) => f,
-            Self::Print(f) => f,
+            Self::Ls(f) => Some(f),
+            Self::Rm(f) => Some(f),
+            Self::Untag(f) => Some(f),
+            Self::Size(f) => Some(f),
+            Self::Print(f) => Some(f),
+            Self::Query(_) | Self::Apply(_) => None,
         }
     }
 }
 
-/// Common filter options for all commands
+/// What `ls`, `rm` and `size` work on, in the order they're worked on
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    /// Stopped containers, running ones are left out
+    Container,
+    #[default]
+    Image,
+    Volume,
+    /// BuildKit's build cache records
+    BuildCache,
+    /// User-defined networks, the runtime's own are left out
+    Network,
+}
+
+impl Kind {
+    pub fn plural(self) -> &'static str {
+        match self {
+            Self::Container => "containers",
+            Self::Image => "images",
+            Self::Volume => "volumes",
+            Self::BuildCache => "build cache records",
+            Self::Network => "networks",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns for reading in a terminal
+    #[default]
+    Table,
+    /// A JSON array of objects
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma separated values with a header row
+    Csv,
+}
+
+/// Options for running a hand written query
 #[derive(Parser, Debug)]
+pub struct QueryOptions {
+    /// File containing the query, reads from stdin if missing or `-`
+    pub file: Option<PathBuf>,
+    /// Query variable as name=value, the value is parsed as JSON falling back to a string
+    #[arg(long = "arg", value_parser = parse_query_arg)]
+    pub args: Vec<(String, FieldValue)>,
+    /// How to print the resulting rows
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+/// Options for applying a cleanup policy
+#[derive(Parser, Debug)]
+pub struct ApplyOptions {
+    /// TOML file listing the rules to apply
+    #[arg(long)]
+    pub policy: PathBuf,
+    /// Only report what each rule matches, even for rules that remove images
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Don't ask for confirmation before a rule removes images
+    #[arg(long, short)]
+    pub yes: bool,
+    /// How many images to remove at once
+    #[arg(long, short, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+    #[command(flatten)]
+    pub protect: ProtectOptions,
+    /// How to print the results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+/// Common filter options for all commands
+#[derive(Parser, Debug, Default)]
 pub struct FilterOptions {
+    /// Whether to look at images, containers, volumes, networks or the build cache. Several
+    /// can be given, e.g. `container,image`, in which case containers are removed first so the
+    /// images, volumes and networks they used can be removed too
+    #[arg(
+        long = "kind",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "image"
+    )]
+    pub kinds: Vec<Kind>,
     /// Only include files created before this timestamp
     #[arg(long)]
     pub created_before: Option<Timestamp>,
     /// Only include files created after this timestamp
     #[arg(long)]
     pub created_after: Option<Timestamp>,
+    /// Only include files older than this, e.g. `30d`, `2w`, `12h` or `P1M`
+    #[arg(long, value_parser = parse_span)]
+    pub older_than: Option<Span>,
+    /// Only include files newer than this, e.g. `30d`, `2w`, `12h` or `P1M`
+    #[arg(long, value_parser = parse_span)]
+    pub newer_than: Option<Span>,
+    /// Only include build cache records last used before this timestamp
+    #[arg(long)]
+    pub used_before: Option<Timestamp>,
+    /// Only include build cache records not used for this long, e.g. `7d`
+    #[arg(long, value_parser = parse_span)]
+    pub unused_for: Option<Span>,
+    /// Only include containers that exited before this timestamp
+    #[arg(long)]
+    pub exited_before: Option<Timestamp>,
+    /// Only include containers that exited with this code, can be given multiple times
+    #[arg(long, value_name = "CODE", allow_negative_numbers = true)]
+    pub exit_code: Vec<i64>,
+    /// Only include containers created from this image, e.g. `ubuntu:24.10`
+    #[arg(long, value_name = "NAME")]
+    pub image: Option<String>,
     /// Only include files whose names match this regex pattern
     #[arg(long)]
     pub name_matches: Option<String>,
@@ -58,15 +188,114 @@ pub struct FilterOptions {
     /// Only include files smaller than this size in bytes
     #[arg(long, value_parser = parse_human_size)]
     pub smaller_than: Option<usize>,
+    /// Only include images from this registry, e.g. `ghcr.io` or `docker.io` for Docker Hub. Can
+    /// be given multiple times
+    #[arg(long, value_name = "HOST")]
+    pub registry: Vec<String>,
+    /// Only include images whose ID starts with this
+    #[arg(long)]
+    pub id_prefix: Option<String>,
+    /// Only include images with this label, can be given multiple times
+    #[arg(long = "label", value_name = "KEY=VALUE", value_parser = parse_label)]
+    pub labels: Vec<String>,
+    /// Skip images with this label, can be given multiple times
+    #[arg(long = "exclude-label", value_name = "KEY=VALUE", value_parser = parse_label)]
+    pub exclude_labels: Vec<String>,
+    /// Also include untagged (dangling) images, these are shown and removed by ID
+    #[arg(long)]
+    pub include_untagged: bool,
+    /// Only include untagged (dangling) images, those without a repository or tag
+    #[arg(long, alias = "dangling")]
+    pub only_untagged: bool,
+    /// Skip the newest N images of each repository, leaving only the older ones
+    #[arg(long, value_name = "N")]
+    pub keep_latest: Option<usize>,
+    /// Only include images that no container (running or stopped) was created from, or
+    /// volumes and networks no container uses
+    #[arg(long)]
+    pub unused: bool,
+    /// Only remove the names the name filters matched, an image with other names is untagged
+    /// rather than deleted
+    #[arg(long)]
+    pub untag: bool,
     /// Doesn't do any operations like RM just lists the images
     #[arg(long)]
     pub dry_run: bool,
+    /// Don't ask for confirmation before removing images
+    #[arg(long, short)]
+    pub yes: bool,
+    /// How many images to remove at once
+    #[arg(long, short, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+    #[command(flatten)]
+    pub protect: ProtectOptions,
     /// Sort any printouts in order
     #[arg(long)]
     pub sort: bool,
+    /// How to print the results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
 }
 
-fn parse_human_size(input: &str) -> Result<usize, String> {
+/// Which images `rm` must leave alone
+#[derive(Parser, Debug, Default)]
+pub struct ProtectOptions {
+    /// Never remove images whose name or ID matches this glob, can be given multiple times
+    #[arg(long, value_name = "PATTERN")]
+    pub protect: Vec<String>,
+    /// TOML file listing protected patterns and labels, defaults to
+    /// `~/.config/docker-cleanup/protect.toml`
+    #[arg(long)]
+    pub protect_file: Option<PathBuf>,
+    /// Remove protected images too
+    #[arg(long)]
+    pub force_protected: bool,
+}
+
+impl FilterOptions {
+    /// The kinds asked for without repeats, in the order they're worked on.
+    pub fn kinds(&self) -> Vec<Kind> {
+        let mut kinds = self.kinds.clone();
+        if kinds.is_empty() {
+            kinds.push(Kind::Image);
+        }
+        kinds.sort();
+        kinds.dedup();
+        kinds
+    }
+
+    /// The latest creation time allowed, the earlier of `--created-before` and `--older-than`.
+    pub fn resolve_created_before(&self, now: &Zoned) -> Option<Timestamp> {
+        let older_than = self.older_than.map(|span| ago(now, span));
+        self.created_before.into_iter().chain(older_than).min()
+    }
+
+    /// The earliest creation time allowed, the later of `--created-after` and `--newer-than`.
+    pub fn resolve_created_after(&self, now: &Zoned) -> Option<Timestamp> {
+        let newer_than = self.newer_than.map(|span| ago(now, span));
+        self.created_after.into_iter().chain(newer_than).max()
+    }
+
+    /// The latest last use allowed, the earlier of `--used-before` and `--unused-for`.
+    pub fn resolve_used_before(&self, now: &Zoned) -> Option<Timestamp> {
+        let unused_for = self.unused_for.map(|span| ago(now, span));
+        self.used_before.into_iter().chain(unused_for).min()
+    }
+}
+
+fn ago(now: &Zoned, span: Span) -> Timestamp {
+    now.checked_sub(span.abs())
+        .map(|then| then.timestamp())
+        .unwrap_or(Timestamp::MIN)
+}
+
+fn parse_span(input: &str) -> Result<Span, String> {
+    input
+        .parse::<Span>()
+        .map_err(|e| format!("Invalid duration '{}': {}", input, e))
+}
+
+pub(crate) fn parse_human_size(input: &str) -> Result<usize, String> {
     match Size::from_str(input) {
         Ok(size) => Ok(size.to_bytes() as usize),
         Err(_) => input
@@ -74,3 +303,21 @@ fn parse_human_size(input: &str) -> Result<usize, String> {
             .map_err(|e| format!("Invalid size '{}': {}", input, e)),
     }
 }
+
+fn parse_query_arg(input: &str) -> Result<(String, FieldValue), String> {
+    let (name, value) = input
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid argument '{}': expected name=value", input))?;
+    let value = match serde_json::from_str::<TransparentValue>(value) {
+        Ok(v) => v.into(),
+        Err(_) => FieldValue::from(value),
+    };
+    Ok((name.to_string(), value))
+}
+
+fn parse_label(input: &str) -> Result<String, String> {
+    match input.split_once('=') {
+        Some((key, _)) if !key.is_empty() => Ok(input.to_string()),
+        _ => Err(format!("Invalid label '{}': expected key=value", input)),
+    }
+}
@@ -0,0 +1,701 @@
+use super::{Backend, ImageRemoval, RuntimeInfo};
+use crate::build_cache::parse_buildx_du;
+use crate::error::{Error, Result};
+use crate::images::{add_labels, merge_names};
+use crate::layers::HistoryOutput;
+use crate::{
+    BuildCache, Container, ContainerDetails, ContainerOutput, Image, ImageOutput, Layer, Network,
+    Volume, docker, podman,
+};
+use jiff::Timestamp;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::process::{Command, Output};
+
+/// What the runtimes print when the CLI is installed but there's no daemon to talk to.
+const UNREACHABLE_MESSAGES: &[&str] = &[
+    "Cannot connect to the Docker daemon",
+    "Is the docker daemon running?",
+    "unable to connect to Podman socket",
+];
+
+/// Runs the command whether or not it succeeds, only failing if it couldn't be started.
+fn spawn(program: &str, args: &[&str]) -> Result<Output> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::RuntimeNotFound(program.to_string()),
+            _ => Error::Io(e),
+        })
+}
+
+fn failure(program: &str, output: &Output) -> Error {
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if UNREACHABLE_MESSAGES.iter().any(|m| message.contains(m)) {
+        Error::DaemonUnreachable {
+            runtime: program.to_string(),
+            message,
+        }
+    } else {
+        Error::Runtime(message)
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<Output> {
+    let output = spawn(program, args)?;
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(failure(program, &output))
+    }
+}
+
+/// Removes all of them with one `image rm`, `container rm` and so on. Both runtimes carry on past ones they
+/// can't remove, printing an error naming each, so those are matched back to the references.
+fn remove_all(program: &str, object: &str, references: &[&str]) -> Vec<Result<()>> {
+    remove_all_printing(program, object, references).0
+}
+
+/// Like [`remove_all`], also returning what the runtime printed about what it removed.
+fn remove_all_printing(
+    program: &str,
+    object: &str,
+    references: &[&str],
+) -> (Vec<Result<()>>, String) {
+    let mut args = vec![object, "rm"];
+    args.extend(references);
+    let output = match spawn(program, &args) {
+        Ok(output) => output,
+        Err(e) => return (fail_all(references, e), String::new()),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if output.status.success() {
+        return (references.iter().map(|_| Ok(())).collect(), stdout);
+    }
+    let results = match failure(program, &output) {
+        Error::Runtime(stderr) => attribute_errors(references, &stderr, &stdout),
+        e => fail_all(references, e),
+    };
+    (results, stdout)
+}
+
+/// Like [`remove_all`] for images, telling the images that were deleted from those only
+/// untagged by the `Untagged: name` and `Deleted: id` lines both runtimes print.
+fn remove_images(program: &str, references: &[&str]) -> Vec<Result<ImageRemoval>> {
+    let (results, stdout) = remove_all_printing(program, "image", references);
+    let deleted = deleted_images(references, &stdout);
+    results
+        .into_iter()
+        .zip(deleted)
+        .map(|(result, deleted)| {
+            result.map(|()| {
+                if deleted {
+                    ImageRemoval::Deleted
+                } else {
+                    ImageRemoval::Untagged
+                }
+            })
+        })
+        .collect()
+}
+
+/// Which of the references deleted an image. A `Deleted` line is about the reference that is
+/// that ID, or else the one most recently untagged. Untagging prints the image's digests too,
+/// which aren't any of the references so don't change which one that is.
+fn deleted_images(references: &[&str], stdout: &str) -> Vec<bool> {
+    let mut deleted = vec![false; references.len()];
+    let mut untagged = None;
+    for line in stdout.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("Untagged:") {
+            if let Some(i) = references.iter().position(|r| *r == name.trim()) {
+                untagged = Some(i);
+            }
+        } else if let Some(id) = line.strip_prefix("Deleted:") {
+            let id = id.trim().trim_start_matches("sha256:");
+            let by_id = references.iter().position(|r| {
+                let r = r.trim_start_matches("sha256:");
+                r.len() >= 12 && id.starts_with(r)
+            });
+            if let Some(i) = by_id.or(untagged) {
+                deleted[i] = true;
+            }
+        }
+    }
+    deleted
+}
+
+fn fail_all(references: &[&str], error: Error) -> Vec<Result<()>> {
+    let message = error.to_string();
+    references
+        .iter()
+        .map(|_| Err(Error::Runtime(message.clone())))
+        .collect()
+}
+
+/// Gives each reference the first error line that names it. Docker's error for a name can
+/// also give the ID of its image, so a line naming both only counts against the name. If no
+/// line names any of them there's no telling what failed, so those the runtime didn't print as
+/// removed all get the whole output.
+fn attribute_errors(references: &[&str], stderr: &str, stdout: &str) -> Vec<Result<()>> {
+    let mut errors = vec![None; references.len()];
+    for line in stderr.lines() {
+        let named = references
+            .iter()
+            .map(|reference| mentions(line, reference))
+            .collect::<Vec<_>>();
+        let names_a_name = named
+            .iter()
+            .zip(references)
+            .any(|(named, reference)| *named && !is_id(reference));
+        for (i, reference) in references.iter().enumerate() {
+            if named[i] && !(names_a_name && is_id(reference)) && errors[i].is_none() {
+                errors[i] = Some(line.trim());
+            }
+        }
+    }
+    if errors.iter().all(Option::is_none) {
+        return references
+            .iter()
+            .map(|reference| {
+                if stdout.lines().any(|line| mentions(line, reference)) {
+                    Ok(())
+                } else {
+                    Err(Error::Runtime(stderr.to_string()))
+                }
+            })
+            .collect();
+    }
+    errors
+        .into_iter()
+        .map(|error| match error {
+            Some(line) => Err(Error::Runtime(line.to_string())),
+            None => Ok(()),
+        })
+        .collect()
+}
+
+/// Whether the line names this reference as a whole word, so `app:1` isn't `work/app:1`. IDs
+/// are shortened to 12 characters in docker's messages, so any word of at least that many
+/// which starts the ID counts.
+fn mentions(line: &str, reference: &str) -> bool {
+    let is_reference_char = |c: char| c.is_ascii_alphanumeric() || "._-/:@".contains(c);
+    let id = reference.trim_start_matches("sha256:");
+    line.split(|c| !is_reference_char(c))
+        // podman prints `reference: message`, and docker ends some messages with a full stop
+        .map(|word| word.trim_end_matches([':', '.']))
+        .any(|word| {
+            let word_id = word.trim_start_matches("sha256:");
+            word == reference
+                || (is_id(reference) && word_id.len() >= 12 && id.starts_with(word_id))
+        })
+}
+
+/// Whether the reference is an image ID rather than a name.
+fn is_id(reference: &str) -> bool {
+    let id = reference.trim_start_matches("sha256:");
+    id.len() >= 12 && id.bytes().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Podman prints a single JSON array whereas docker prints one JSON object per line.
+fn run_json<T: DeserializeOwned>(program: &str, args: &[&str], is_podman: bool) -> Result<Vec<T>> {
+    let output = run(program, args)?;
+    let command = || format!("{program} {}", args.join(" "));
+    let s = String::from_utf8_lossy(&output.stdout);
+    if is_podman {
+        serde_json::from_str(&s).map_err(|source| Error::UnparsableOutput {
+            command: command(),
+            line: s
+                .lines()
+                .nth(source.line().saturating_sub(1))
+                .unwrap_or_default()
+                .to_string(),
+            source: source.into(),
+        })
+    } else {
+        let mut v = vec![];
+        for line in s.lines() {
+            let value = serde_json::from_str(line).map_err(|source| Error::UnparsableOutput {
+                command: command(),
+                line: line.to_string(),
+                source: source.into(),
+            })?;
+            v.push(value);
+        }
+        Ok(v)
+    }
+}
+
+/// The most images passed to one `image inspect`, which keeps the command line a sane length.
+const MAX_INSPECT: usize = 100;
+
+/// Inspects the images, with as few `image inspect`s as a sane command line allows.
+fn inspect_images(program: &str, ids: &[&str]) -> Result<Vec<docker::ImageInspect>> {
+    let mut inspected = vec![];
+    for ids in ids.chunks(MAX_INSPECT) {
+        let mut args = vec!["image", "inspect"];
+        args.extend(ids);
+        // inspect prints a JSON array whichever runtime it is
+        inspected.extend(run_json::<docker::ImageInspect>(program, &args, true)?);
+    }
+    Ok(inspected)
+}
+
+/// Pairs up the layers from `image inspect` with their sizes from `image history`.
+fn layers(program: &str, id: &str, is_podman: bool) -> Result<Vec<Layer>> {
+    // inspect prints a JSON array whichever runtime it is
+    let inspect: Vec<docker::ImageInspect> = run_json(program, &["image", "inspect", id], true)?;
+    let history: Vec<HistoryOutput> = run_json(
+        program,
+        &[
+            "image",
+            "history",
+            "--no-trunc",
+            "--human=false",
+            "--format",
+            "json",
+            id,
+        ],
+        is_podman,
+    )?;
+    let digests = inspect
+        .into_iter()
+        .next()
+        .map(|i| i.root_fs.layers)
+        .unwrap_or_default();
+    Layer::from_history(id, digests, history.iter().map(HistoryOutput::size))
+}
+
+/// Every image's layer digests from a single `image inspect`, or as few as it takes.
+fn layer_digests(program: &str, images: &[&Image]) -> Result<Vec<Vec<String>>> {
+    let ids = images.iter().map(|i| i.hash.as_str()).collect::<Vec<_>>();
+    let mut digests = inspect_images(program, &ids)?
+        .into_iter()
+        .map(|i| {
+            (
+                i.id.trim_start_matches("sha256:").to_string(),
+                i.root_fs.layers,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    Ok(images
+        .iter()
+        .map(|i| digests.remove(&i.hash).unwrap_or_default())
+        .collect())
+}
+
+/// Inspects the containers with `--size` for their writable layers, which `ps --size` would
+/// work out for every container rather than just these.
+fn container_details(program: &str, ids: &[&str]) -> Result<Vec<ContainerDetails>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut args = vec!["container", "inspect", "--size"];
+    args.extend(ids);
+    // inspect prints a JSON array whichever runtime it is
+    let inspected: Vec<docker::ContainerInspect> = run_json(program, &args, true)?;
+    Ok(inspected.into_iter().map(ContainerDetails::from).collect())
+}
+
+/// Inspects every volume, `volume ls` leaves out the creation time. Neither says whether a
+/// container uses the volume, only that it's dangling when none does.
+fn volumes(program: &str) -> Result<Vec<Volume>> {
+    let names = |extra: &[&str]| -> Result<Vec<String>> {
+        let mut args = vec!["volume", "ls", "--quiet"];
+        args.extend(extra);
+        let output = run(program, &args)?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect())
+    };
+    let all = names(&[])?;
+    if all.is_empty() {
+        return Ok(vec![]);
+    }
+    let unused = names(&["--filter", "dangling=true"])?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let mut args = vec!["volume", "inspect"];
+    args.extend(all.iter().map(String::as_str));
+    // inspect prints a JSON array whichever runtime it is
+    let inspected: Vec<docker::Volume> = run_json(program, &args, true)?;
+    Ok(inspected
+        .into_iter()
+        .map(|v| {
+            let mut volume = Volume::from(v);
+            volume.in_use = !unused.contains(&volume.name);
+            volume
+        })
+        .collect())
+}
+
+/// `network ls` prints labels and the creation time as text for docker, so every network is
+/// inspected instead.
+fn docker_networks(program: &str) -> Result<Vec<Network>> {
+    let output = run(program, &["network", "ls", "--quiet", "--no-trunc"])?;
+    let ids = String::from_utf8_lossy(&output.stdout).into_owned();
+    let ids = ids.split_whitespace().collect::<Vec<_>>();
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut args = vec!["network", "inspect"];
+    args.extend(ids);
+    let networks: Vec<docker::Network> = run_json(program, &args, true)?;
+    Ok(networks.into_iter().map(Network::from).collect())
+}
+
+/// Podman's `network ls` has everything, as a JSON array in its own shape.
+fn podman_networks(program: &str) -> Result<Vec<Network>> {
+    let networks: Vec<podman::Network> =
+        run_json(program, &["network", "ls", "--format", "json"], true)?;
+    Ok(networks.into_iter().map(Network::from).collect())
+}
+
+/// Lists the build cache with `docker buildx du --verbose`, the only listing with exact
+/// creation times and sizes.
+fn build_cache(program: &str) -> Result<Vec<BuildCache>> {
+    let args = ["buildx", "du", "--verbose"];
+    let output = run(program, &args)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_buildx_du(&stdout, Timestamp::now()).map_err(|(line, source)| Error::UnparsableOutput {
+        command: format!("{program} {}", args.join(" ")),
+        line,
+        source,
+    })
+}
+
+/// Prunes one build cache record. `buildx prune` succeeds whether or not it pruned anything,
+/// so it only counts if the record is listed as reclaimed.
+fn prune_build_cache(program: &str, id: &str) -> Result<()> {
+    let filter = format!("id={id}");
+    let output = run(
+        program,
+        &["buildx", "prune", "--force", "--filter", &filter],
+    )?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.lines().any(|line| mentions(line, id)) {
+        Ok(())
+    } else {
+        Err(Error::Runtime(format!(
+            "build cache record {id} wasn't pruned, it may be in use"
+        )))
+    }
+}
+
+/// Parses `Docker version 28.3.2, build 578ccf6` or `podman version 5.5.2`.
+fn runtime_info(program: &str) -> Result<RuntimeInfo> {
+    let output = run(program, &["--version"])?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    let mut words = version.split_whitespace();
+    let name = words.next().unwrap_or(program).to_lowercase();
+    let version = words
+        .skip_while(|w| *w != "version")
+        .nth(1)
+        .unwrap_or_default()
+        .trim_end_matches(',')
+        .to_string();
+    Ok(RuntimeInfo { name, version })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DockerCli {
+    /// `docker` is sometimes podman's compatibility shim which prints podman's JSON
+    is_podman: bool,
+}
+
+impl DockerCli {
+    pub fn detect() -> Self {
+        let is_podman = runtime_info("docker").is_ok_and(|info| info.name == "podman");
+        Self { is_podman }
+    }
+
+    pub fn is_available() -> bool {
+        runtime_info("docker").is_ok()
+    }
+}
+
+impl Backend for DockerCli {
+    fn info(&self) -> Result<RuntimeInfo> {
+        runtime_info("docker")
+    }
+
+    fn images(&self) -> Result<Vec<Image>> {
+        let images: Vec<ImageOutput> = run_json(
+            "docker",
+            &["image", "ls", "--no-trunc", "--format", "json"],
+            self.is_podman,
+        )?;
+        let mut images = merge_names(images.into_iter().map(Image::from).collect());
+        // docker's listing has no labels, and protection relies on them so they're not optional
+        if !self.is_podman && !images.is_empty() {
+            let ids = images.iter().map(|i| i.hash.as_str()).collect::<Vec<_>>();
+            let inspected = inspect_images("docker", &ids)?;
+            add_labels(&mut images, inspected);
+        }
+        Ok(images)
+    }
+
+    fn containers(&self) -> Result<Vec<Container>> {
+        let containers: Vec<ContainerOutput> = run_json(
+            "docker",
+            &["ps", "--all", "--format", "json"],
+            self.is_podman,
+        )?;
+        Ok(containers.into_iter().map(Container::from).collect())
+    }
+
+    fn container_details(&self, ids: &[&str]) -> Result<Vec<ContainerDetails>> {
+        container_details("docker", ids)
+    }
+
+    fn remove_container(&self, id: &str) -> Result<()> {
+        run("docker", &["container", "rm", id]).map(|_| ())
+    }
+
+    fn remove_containers(&self, ids: &[&str]) -> Vec<Result<()>> {
+        remove_all("docker", "container", ids)
+    }
+
+    fn layers(&self, image: &Image) -> Result<Vec<Layer>> {
+        layers("docker", &image.hash, self.is_podman)
+    }
+
+    fn layer_digests(&self, images: &[&Image]) -> Result<Vec<Vec<String>>> {
+        layer_digests("docker", images)
+    }
+
+    fn remove_image(&self, reference: &str) -> Result<ImageRemoval> {
+        remove_images("docker", &[reference]).remove(0)
+    }
+
+    fn remove_images(&self, references: &[&str]) -> Vec<Result<ImageRemoval>> {
+        remove_images("docker", references)
+    }
+
+    fn volumes(&self) -> Result<Vec<Volume>> {
+        volumes("docker")
+    }
+
+    fn remove_volume(&self, name: &str) -> Result<()> {
+        run("docker", &["volume", "rm", name]).map(|_| ())
+    }
+
+    fn remove_volumes(&self, names: &[&str]) -> Vec<Result<()>> {
+        remove_all("docker", "volume", names)
+    }
+
+    fn networks(&self) -> Result<Vec<Network>> {
+        if self.is_podman {
+            return podman_networks("docker");
+        }
+        docker_networks("docker")
+    }
+
+    fn remove_network(&self, id: &str) -> Result<()> {
+        run("docker", &["network", "rm", id]).map(|_| ())
+    }
+
+    fn remove_networks(&self, ids: &[&str]) -> Vec<Result<()>> {
+        remove_all("docker", "network", ids)
+    }
+
+    /// The podman shim has no BuildKit so no build cache.
+    fn build_cache(&self) -> Result<Vec<BuildCache>> {
+        if self.is_podman {
+            return Ok(vec![]);
+        }
+        build_cache("docker")
+    }
+
+    fn remove_build_cache(&self, id: &str) -> Result<()> {
+        prune_build_cache("docker", id)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PodmanCli;
+
+impl PodmanCli {
+    pub fn is_available() -> bool {
+        runtime_info("podman").is_ok()
+    }
+}
+
+impl Backend for PodmanCli {
+    fn info(&self) -> Result<RuntimeInfo> {
+        runtime_info("podman")
+    }
+
+    fn images(&self) -> Result<Vec<Image>> {
+        let images: Vec<ImageOutput> =
+            run_json("podman", &["image", "ls", "--format", "json"], true)?;
+        Ok(images.into_iter().map(Image::from).collect())
+    }
+
+    fn containers(&self) -> Result<Vec<Container>> {
+        let containers: Vec<ContainerOutput> =
+            run_json("podman", &["ps", "--all", "--format", "json"], true)?;
+        Ok(containers.into_iter().map(Container::from).collect())
+    }
+
+    fn container_details(&self, ids: &[&str]) -> Result<Vec<ContainerDetails>> {
+        container_details("podman", ids)
+    }
+
+    fn remove_container(&self, id: &str) -> Result<()> {
+        run("podman", &["container", "rm", id]).map(|_| ())
+    }
+
+    fn remove_containers(&self, ids: &[&str]) -> Vec<Result<()>> {
+        remove_all("podman", "container", ids)
+    }
+
+    fn layers(&self, image: &Image) -> Result<Vec<Layer>> {
+        layers("podman", &image.hash, true)
+    }
+
+    fn layer_digests(&self, images: &[&Image]) -> Result<Vec<Vec<String>>> {
+        layer_digests("podman", images)
+    }
+
+    fn remove_image(&self, reference: &str) -> Result<ImageRemoval> {
+        remove_images("podman", &[reference]).remove(0)
+    }
+
+    fn remove_images(&self, references: &[&str]) -> Vec<Result<ImageRemoval>> {
+        remove_images("podman", references)
+    }
+
+    fn volumes(&self) -> Result<Vec<Volume>> {
+        volumes("podman")
+    }
+
+    fn remove_volume(&self, name: &str) -> Result<()> {
+        run("podman", &["volume", "rm", name]).map(|_| ())
+    }
+
+    fn remove_volumes(&self, names: &[&str]) -> Vec<Result<()>> {
+        remove_all("podman", "volume", names)
+    }
+
+    fn networks(&self) -> Result<Vec<Network>> {
+        podman_networks("podman")
+    }
+
+    fn remove_network(&self, id: &str) -> Result<()> {
+        run("podman", &["network", "rm", id]).map(|_| ())
+    }
+
+    fn remove_networks(&self, ids: &[&str]) -> Vec<Result<()>> {
+        remove_all("podman", "network", ids)
+    }
+
+    /// Podman builds with buildah, which keeps its cache as ordinary images.
+    fn build_cache(&self) -> Result<Vec<BuildCache>> {
+        Ok(vec![])
+    }
+
+    fn remove_build_cache(&self, id: &str) -> Result<()> {
+        Err(Error::Runtime(format!(
+            "podman has no build cache record {id}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_errors_are_attributed() {
+        let references = [
+            "ubuntu:24.10",
+            "app:1",
+            "work/app:1",
+            "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0",
+        ];
+        let docker = "Error response from daemon: conflict: unable to remove repository reference \"ubuntu:24.10\" (must force) - container 3f4e0c1d2b6a is using its referenced image e9d2252ab371
+Error response from daemon: No such image: work/app:1
+Error response from daemon: conflict: unable to delete 0f1e2d3c4b5a (cannot be forced) - image has dependent child images";
+        let results = attribute_errors(&references, docker, "");
+        assert!(
+            results[0]
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .contains("3f4e0c1d2b6a")
+        );
+        assert!(results[1].is_ok());
+        assert!(
+            results[2]
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .contains("No such image")
+        );
+        assert!(
+            results[3]
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .contains("dependent child")
+        );
+
+        let podman = "Error: 1 error occurred:\n\t* app:1: image not known";
+        let results = attribute_errors(&references, podman, "");
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+
+        let results = attribute_errors(&references[..2], "Error: something went wrong", "");
+        assert!(results.iter().all(|r| r.is_err()));
+        // What the runtime printed as removed wasn't what failed
+        let results = attribute_errors(
+            &references[..2],
+            "Error: something went wrong",
+            "Untagged: ubuntu:24.10\n",
+        );
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+
+        // The error for a name gives its image's ID too, which isn't what failed
+        let references = [
+            "ubuntu:24.10",
+            "e9d2252ab371a1149d3ef64b7793a274375dee5d9ec61b9e4fb41d75f156c1a1",
+        ];
+        let docker = "Error response from daemon: conflict: unable to remove repository reference \"ubuntu:24.10\" (must force) - container 3f4e0c1d2b6a is using its referenced image e9d2252ab371";
+        let results = attribute_errors(&references, docker, "");
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+        // Nor does part of a longer word count
+        assert!(!mentions(
+            "No such image: ubuntu:24.10-slim",
+            "ubuntu:24.10"
+        ));
+        assert!(!mentions("No such image: work/app:1", "app:1"));
+    }
+
+    #[test]
+    fn deleted_images_are_told_from_untagged() {
+        let references = [
+            "work/app:pr-7",
+            "work/app:pr-8",
+            "base:1",
+            "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0",
+        ];
+        // base:1 has child images so is only untagged
+        let docker = "Untagged: work/app:pr-7
+Untagged: work/app:pr-8
+Untagged: work/app@sha256:cdf755952ed117f6126ff4e65810bf93767d4c38f5c7185b50ec1f1078b464cc
+Deleted: sha256:1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a2b
+Deleted: sha256:9f8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a39281706f5e4d3c2b1a0
+Untagged: base:1
+Deleted: sha256:0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0";
+        assert_eq!(
+            deleted_images(&references, docker),
+            [false, true, false, true]
+        );
+    }
+}
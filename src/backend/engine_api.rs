@@ -0,0 +1,130 @@
+use super::{Backend, ImageRemoval, RuntimeInfo};
+use crate::error::Result;
+use crate::{BuildCache, Container, ContainerDetails, Image, Layer, Network, Volume, engine};
+
+#[derive(Debug, Clone)]
+pub struct EngineApi {
+    client: engine::Client,
+}
+
+impl EngineApi {
+    pub fn new(client: engine::Client) -> Self {
+        Self { client }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        engine::Client::from_env().map(Self::new)
+    }
+
+    pub fn connect() -> Option<Self> {
+        engine::Client::connect().map(Self::new)
+    }
+}
+
+fn image_removal(deleted: bool) -> ImageRemoval {
+    if deleted {
+        ImageRemoval::Deleted
+    } else {
+        ImageRemoval::Untagged
+    }
+}
+
+impl Backend for EngineApi {
+    fn info(&self) -> Result<RuntimeInfo> {
+        let version = self.client.version()?;
+        Ok(RuntimeInfo {
+            name: version.platform.name,
+            version: version.version,
+        })
+    }
+
+    fn images(&self) -> Result<Vec<Image>> {
+        let images = self.client.images()?;
+        Ok(images.into_iter().map(Image::from).collect())
+    }
+
+    fn containers(&self) -> Result<Vec<Container>> {
+        let containers = self.client.containers()?;
+        Ok(containers.into_iter().map(Container::from).collect())
+    }
+
+    fn container_details(&self, ids: &[&str]) -> Result<Vec<ContainerDetails>> {
+        ids.iter()
+            .map(|id| {
+                self.client
+                    .inspect_container(id)
+                    .map(ContainerDetails::from)
+            })
+            .collect()
+    }
+
+    fn remove_container(&self, id: &str) -> Result<()> {
+        self.client.remove_container(id)
+    }
+
+    fn remove_containers(&self, ids: &[&str]) -> Vec<Result<()>> {
+        self.client.remove_containers(ids)
+    }
+
+    fn layers(&self, image: &Image) -> Result<Vec<Layer>> {
+        let inspect = self.client.inspect_image(&image.hash)?;
+        let history = self.client.image_history(&image.hash)?;
+        Layer::from_history(
+            &image.hash,
+            inspect.root_fs.layers,
+            history.iter().map(|h| h.size.max(0) as u64),
+        )
+    }
+
+    /// The API can only inspect one image per request, but at least skips the history.
+    fn layer_digests(&self, images: &[&Image]) -> Result<Vec<Vec<String>>> {
+        images
+            .iter()
+            .map(|i| self.client.inspect_image(&i.hash).map(|x| x.root_fs.layers))
+            .collect()
+    }
+
+    fn remove_image(&self, reference: &str) -> Result<ImageRemoval> {
+        self.client.remove_image(reference).map(image_removal)
+    }
+
+    fn remove_images(&self, references: &[&str]) -> Vec<Result<ImageRemoval>> {
+        let results = self.client.remove_images(references);
+        results.into_iter().map(|r| r.map(image_removal)).collect()
+    }
+
+    fn volumes(&self) -> Result<Vec<Volume>> {
+        let volumes = self.client.volumes()?;
+        Ok(volumes.into_iter().map(Volume::from).collect())
+    }
+
+    fn remove_volume(&self, name: &str) -> Result<()> {
+        self.client.remove_volume(name)
+    }
+
+    fn remove_volumes(&self, names: &[&str]) -> Vec<Result<()>> {
+        self.client.remove_volumes(names)
+    }
+
+    fn networks(&self) -> Result<Vec<Network>> {
+        let networks = self.client.networks()?;
+        Ok(networks.into_iter().map(Network::from).collect())
+    }
+
+    fn remove_network(&self, id: &str) -> Result<()> {
+        self.client.remove_network(id)
+    }
+
+    fn remove_networks(&self, ids: &[&str]) -> Vec<Result<()>> {
+        self.client.remove_networks(ids)
+    }
+
+    fn build_cache(&self) -> Result<Vec<BuildCache>> {
+        let records = self.client.build_cache()?;
+        Ok(records.into_iter().map(BuildCache::from).collect())
+    }
+
+    fn remove_build_cache(&self, id: &str) -> Result<()> {
+        self.client.prune_build_cache(id)
+    }
+}
@@ -0,0 +1,298 @@
+use super::{Backend, ImageRemoval, RuntimeInfo};
+use crate::error::{Error, Result};
+use crate::{BuildCache, Container, ContainerDetails, Image, Layer, Network, Volume};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// An in-memory runtime so queries and removals can be tested without a daemon. Removing an
+/// image that a container uses or that other images are built on fails the same way docker
+/// does, as does removing a volume or network a container uses, or a running container.
+#[derive(Debug, Default)]
+pub struct Fixture {
+    images: Mutex<Vec<Image>>,
+    containers: Mutex<Vec<Container>>,
+    details: BTreeMap<String, ContainerDetails>,
+    layers: BTreeMap<String, Vec<Layer>>,
+    /// IDs of the images whose layers fail to be looked up
+    broken_layers: Vec<String>,
+    volumes: Mutex<Vec<Volume>>,
+    build_cache: Mutex<Vec<BuildCache>>,
+    networks: Mutex<Vec<Network>>,
+    removed: Mutex<Vec<String>>,
+    /// IDs of the images whose layer sizes were looked up
+    layer_lookups: Mutex<Vec<String>>,
+}
+
+impl Fixture {
+    /// Images given without any references go by their repository and tag.
+    pub fn new(mut images: Vec<Image>, containers: Vec<Container>) -> Self {
+        for image in &mut images {
+            if image.references.is_empty() && !image.is_dangling() {
+                image.references = vec![format!("{}:{}", image.repository, image.tag)];
+            }
+        }
+        Self {
+            images: Mutex::new(images),
+            containers: Mutex::new(containers),
+            details: BTreeMap::new(),
+            layers: BTreeMap::new(),
+            broken_layers: vec![],
+            volumes: Mutex::default(),
+            build_cache: Mutex::default(),
+            networks: Mutex::default(),
+            removed: Mutex::default(),
+            layer_lookups: Mutex::default(),
+        }
+    }
+
+    /// Gives the image with this hash the layers, as `(digest, size)`.
+    pub fn with_layers(mut self, hash: &str, layers: &[(&str, u64)]) -> Self {
+        let layers = layers
+            .iter()
+            .map(|(digest, size)| Layer {
+                digest: digest.to_string(),
+                size: *size,
+            })
+            .collect();
+        self.layers.insert(hash.to_string(), layers);
+        self
+    }
+
+    /// Makes looking up the layers of the image with this hash fail.
+    pub fn with_broken_layers(mut self, hash: &str) -> Self {
+        self.broken_layers.push(hash.to_string());
+        self
+    }
+
+    /// Adds to the containers given to [`Fixture::new`].
+    pub fn with_containers(self, containers: Vec<Container>) -> Self {
+        self.containers.lock().unwrap().extend(containers);
+        self
+    }
+
+    pub fn with_details(mut self, details: Vec<ContainerDetails>) -> Self {
+        self.details
+            .extend(details.into_iter().map(|d| (d.id.clone(), d)));
+        self
+    }
+
+    pub fn with_volumes(self, volumes: Vec<Volume>) -> Self {
+        *self.volumes.lock().unwrap() = volumes;
+        self
+    }
+
+    pub fn with_build_cache(self, records: Vec<BuildCache>) -> Self {
+        *self.build_cache.lock().unwrap() = records;
+        self
+    }
+
+    pub fn with_networks(self, networks: Vec<Network>) -> Self {
+        *self.networks.lock().unwrap() = networks;
+        self
+    }
+
+    /// The references passed to successful `remove_*` calls, in order.
+    pub fn removed(&self) -> Vec<String> {
+        self.removed.lock().unwrap().clone()
+    }
+
+    pub fn layer_lookups(&self) -> Vec<String> {
+        self.layer_lookups.lock().unwrap().clone()
+    }
+}
+
+fn matches_reference(image: &Image, reference: &str) -> bool {
+    image.hash == reference || image.references.iter().any(|r| names_match(r, reference))
+}
+
+fn names_match(name: &str, reference: &str) -> bool {
+    name == reference || name.strip_suffix(":latest") == Some(reference)
+}
+
+impl Backend for Fixture {
+    fn info(&self) -> Result<RuntimeInfo> {
+        Ok(RuntimeInfo {
+            name: "fixture".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+
+    fn images(&self) -> Result<Vec<Image>> {
+        Ok(self.images.lock().unwrap().clone())
+    }
+
+    fn containers(&self) -> Result<Vec<Container>> {
+        Ok(self.containers.lock().unwrap().clone())
+    }
+
+    fn container_details(&self, ids: &[&str]) -> Result<Vec<ContainerDetails>> {
+        Ok(ids
+            .iter()
+            .map(|id| {
+                self.details.get(*id).cloned().unwrap_or(ContainerDetails {
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+            })
+            .collect())
+    }
+
+    fn remove_container(&self, id: &str) -> Result<()> {
+        let mut containers = self.containers.lock().unwrap();
+        let Some(index) = containers.iter().position(|c| c.id == id) else {
+            return Err(Error::Runtime(format!("No such container: {id}")));
+        };
+        if containers[index].is_running() {
+            return Err(Error::Runtime(format!(
+                "cannot remove container \"{id}\": container is running: stop the container before removing"
+            )));
+        }
+        containers.remove(index);
+        self.removed.lock().unwrap().push(id.to_string());
+        Ok(())
+    }
+
+    fn layers(&self, image: &Image) -> Result<Vec<Layer>> {
+        self.layer_lookups.lock().unwrap().push(image.hash.clone());
+        if self.broken_layers.contains(&image.hash) {
+            return Err(Error::Runtime(format!(
+                "image {} has 2 layers but 3 history steps with a size, so their sizes are unknown",
+                image.hash
+            )));
+        }
+        Ok(self.layers.get(&image.hash).cloned().unwrap_or_default())
+    }
+
+    fn layer_digests(&self, images: &[&Image]) -> Result<Vec<Vec<String>>> {
+        Ok(images
+            .iter()
+            .map(|i| {
+                let layers = self.layers.get(&i.hash).into_iter().flatten();
+                layers.map(|l| l.digest.clone()).collect()
+            })
+            .collect())
+    }
+
+    fn remove_image(&self, reference: &str) -> Result<ImageRemoval> {
+        let mut images = self.images.lock().unwrap();
+        let Some(index) = images.iter().position(|i| matches_reference(i, reference)) else {
+            return Err(Error::Runtime(format!("No such image: {reference}")));
+        };
+        // Only removing the last name deletes the image, before that it's just untagged
+        let image = &mut images[index];
+        if image.references.len() > 1 {
+            if image.hash == reference {
+                return Err(Error::Runtime(format!(
+                    "conflict: unable to delete {} (must be forced) - image is referenced in multiple repositories",
+                    &reference[..reference.len().min(12)]
+                )));
+            }
+            image.references.retain(|r| !names_match(r, reference));
+            if let Some((repository, tag)) = image.references[0].rsplit_once(':') {
+                image.repository = repository.to_string();
+                image.tag = tag.to_string();
+            }
+            self.removed.lock().unwrap().push(reference.to_string());
+            return Ok(ImageRemoval::Untagged);
+        }
+        if let Some(c) = self
+            .containers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|c| c.uses_image(&images[index]))
+        {
+            return Err(Error::Runtime(format!(
+                "conflict: unable to remove repository reference \"{reference}\" - container {} is using its referenced image",
+                c.id
+            )));
+        }
+        let hash = &images[index].hash;
+        if images.iter().any(|i| i.parent.as_ref() == Some(hash)) {
+            // Like docker, the last name of an image others are built on is just untagged
+            if *hash != reference {
+                let image = &mut images[index];
+                image.references.clear();
+                image.repository.clear();
+                image.tag.clear();
+                self.removed.lock().unwrap().push(reference.to_string());
+                return Ok(ImageRemoval::Untagged);
+            }
+            return Err(Error::Runtime(format!(
+                "conflict: unable to delete {} (cannot be forced) - image has dependent child images",
+                &hash[..hash.len().min(12)]
+            )));
+        }
+        images.remove(index);
+        self.removed.lock().unwrap().push(reference.to_string());
+        Ok(ImageRemoval::Deleted)
+    }
+
+    fn volumes(&self) -> Result<Vec<Volume>> {
+        Ok(self.volumes.lock().unwrap().clone())
+    }
+
+    fn remove_volume(&self, name: &str) -> Result<()> {
+        let mut volumes = self.volumes.lock().unwrap();
+        let Some(index) = volumes.iter().position(|v| v.name == name) else {
+            return Err(Error::Runtime(format!("get {name}: no such volume")));
+        };
+        if volumes[index].in_use {
+            return Err(Error::Runtime(format!("remove {name}: volume is in use")));
+        }
+        volumes.remove(index);
+        self.removed.lock().unwrap().push(name.to_string());
+        Ok(())
+    }
+
+    fn networks(&self) -> Result<Vec<Network>> {
+        Ok(self.networks.lock().unwrap().clone())
+    }
+
+    fn remove_network(&self, id: &str) -> Result<()> {
+        let mut networks = self.networks.lock().unwrap();
+        let Some(index) = networks.iter().position(|n| n.id == id) else {
+            return Err(Error::Runtime(format!("network {id} not found")));
+        };
+        let network = &networks[index];
+        if network.is_predefined() {
+            return Err(Error::Runtime(format!(
+                "{} is a pre-defined network and cannot be removed",
+                network.name
+            )));
+        }
+        if self
+            .containers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|c| network.is_attached(c))
+        {
+            return Err(Error::Runtime(format!(
+                "error while removing network: network {} id {id} has active endpoints",
+                network.name
+            )));
+        }
+        networks.remove(index);
+        self.removed.lock().unwrap().push(id.to_string());
+        Ok(())
+    }
+
+    fn build_cache(&self) -> Result<Vec<BuildCache>> {
+        Ok(self.build_cache.lock().unwrap().clone())
+    }
+
+    fn remove_build_cache(&self, id: &str) -> Result<()> {
+        let mut records = self.build_cache.lock().unwrap();
+        match records.iter().position(|r| r.id == id && !r.in_use) {
+            Some(index) => {
+                records.remove(index);
+                self.removed.lock().unwrap().push(id.to_string());
+                Ok(())
+            }
+            None => Err(Error::Runtime(format!(
+                "build cache record {id} wasn't pruned, it may be in use"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! The container runtimes we know how to talk to. Everything that lists or removes things
+//! goes through a [`Backend`] so the adapter and `rm` don't care which one is in use.
+use crate::args::Runtime;
+use crate::error::{Error, Result};
+use crate::{BuildCache, Container, ContainerDetails, Image, Layer, Network, Volume};
+use std::sync::Arc;
+
+mod cli;
+mod engine_api;
+#[cfg(test)]
+mod fixture;
+
+pub use cli::{DockerCli, PodmanCli};
+pub use engine_api::EngineApi;
+#[cfg(test)]
+pub use fixture::Fixture;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeInfo {
+    pub name: String,
+    pub version: String,
+}
+
+/// What removing an image by one of its names did. Only removing the last name deletes an image,
+/// and not even then if other images are built on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageRemoval {
+    Untagged,
+    Deleted,
+}
+
+pub trait Backend: std::fmt::Debug + Send + Sync {
+    fn info(&self) -> Result<RuntimeInfo>;
+
+    fn images(&self) -> Result<Vec<Image>>;
+
+    fn containers(&self) -> Result<Vec<Container>>;
+
+    /// Exit codes, finish times and writable-layer sizes of the containers, which need them
+    /// inspected so aren't part of [`Backend::containers`].
+    fn container_details(&self, ids: &[&str]) -> Result<Vec<ContainerDetails>>;
+
+    /// Removes a stopped container by ID.
+    fn remove_container(&self, id: &str) -> Result<()>;
+
+    /// Like [`Backend::remove_images`] but for containers.
+    fn remove_containers(&self, ids: &[&str]) -> Vec<Result<()>> {
+        ids.iter().map(|id| self.remove_container(id)).collect()
+    }
+
+    /// The layers the image is made of with their sizes, oldest first.
+    fn layers(&self, image: &Image) -> Result<Vec<Layer>>;
+
+    /// Just the digests of each image's layers, oldest first and in the same order as `images`.
+    /// Unlike [`Backend::layers`] this doesn't need the image history, so backends look them all
+    /// up at once where they can.
+    fn layer_digests(&self, images: &[&Image]) -> Result<Vec<Vec<String>>>;
+
+    /// Removes an image by name or ID, saying whether the runtime deleted it or only untagged it.
+    fn remove_image(&self, reference: &str) -> Result<ImageRemoval>;
+
+    /// Removes several images, returning the result for each reference in the same order.
+    /// Backends override this when they can do it in fewer round trips.
+    fn remove_images(&self, references: &[&str]) -> Vec<Result<ImageRemoval>> {
+        references.iter().map(|r| self.remove_image(r)).collect()
+    }
+
+    fn volumes(&self) -> Result<Vec<Volume>>;
+
+    /// Removes a volume by name.
+    fn remove_volume(&self, name: &str) -> Result<()>;
+
+    /// Like [`Backend::remove_images`] but for volumes.
+    fn remove_volumes(&self, names: &[&str]) -> Vec<Result<()>> {
+        names.iter().map(|n| self.remove_volume(n)).collect()
+    }
+
+    fn networks(&self) -> Result<Vec<Network>>;
+
+    /// Removes a network by ID.
+    fn remove_network(&self, id: &str) -> Result<()>;
+
+    /// Like [`Backend::remove_images`] but for networks.
+    fn remove_networks(&self, ids: &[&str]) -> Vec<Result<()>> {
+        ids.iter().map(|id| self.remove_network(id)).collect()
+    }
+
+    /// BuildKit's cache records, runtimes without BuildKit have none.
+    fn build_cache(&self) -> Result<Vec<BuildCache>>;
+
+    /// Prunes a build cache record by ID.
+    fn remove_build_cache(&self, id: &str) -> Result<()>;
+}
+
+/// Picks the backend for the requested runtime. With `Auto` the Engine API is preferred if
+/// the socket is reachable, then the `docker` CLI and finally the `podman` CLI.
+pub fn select(runtime: Runtime) -> Result<Arc<dyn Backend>> {
+    let backend: Arc<dyn Backend> = match runtime {
+        Runtime::Docker => Arc::new(DockerCli::detect()),
+        Runtime::Podman => Arc::new(PodmanCli),
+        Runtime::Engine => Arc::new(EngineApi::from_env().ok_or_else(|| {
+            Error::RuntimeNotFound("no Docker Engine API socket, is DOCKER_HOST set?".to_string())
+        })?),
+        Runtime::Auto => {
+            if let Some(engine) = EngineApi::connect() {
+                Arc::new(engine)
+            } else if DockerCli::is_available() {
+                Arc::new(DockerCli::detect())
+            } else if PodmanCli::is_available() {
+                Arc::new(PodmanCli)
+            } else {
+                return Err(Error::RuntimeNotFound(
+                    "tried the Docker Engine API socket, docker and podman".to_string(),
+                ));
+            }
+        }
+    };
+    Ok(backend)
+}
@@ -0,0 +1,404 @@
+use crate::args::FilterOptions;
+use crate::backend::Backend;
+use crate::error;
+use crate::output::{BuildCacheRecord, BuildCacheSizeSummary, Row};
+use crate::{QueryArgs, Record, engine};
+use jiff::Zoned;
+use jiff::fmt::strtime::BrokenDownTime;
+use jiff::{SignedDuration, Timestamp};
+use std::collections::BTreeMap;
+
+/// A BuildKit cache record, a layer or mount left behind by a build that later builds can reuse.
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct BuildCache {
+    pub id: String,
+    /// `regular`, `source.local`, `exec.cachemount` and so on
+    pub cache_type: String,
+    pub size: u64,
+    /// Whether the data is shared with something else, like an image, so pruning the record
+    /// won't free it
+    pub shared: bool,
+    /// Whether a running build is using it, BuildKit won't prune it until that's done
+    pub in_use: bool,
+    pub created_at: Timestamp,
+    /// `None` if it hasn't been used since the build that created it
+    pub last_used_at: Option<Timestamp>,
+    pub usage_count: u64,
+    /// What made the record, e.g. the Dockerfile step
+    pub description: String,
+}
+
+impl BuildCache {
+    /// When the record was last used, counting being created as a use.
+    pub fn last_active(&self) -> Timestamp {
+        self.last_used_at.unwrap_or(self.created_at)
+    }
+}
+
+impl From<engine::BuildCache> for BuildCache {
+    fn from(c: engine::BuildCache) -> Self {
+        Self {
+            id: c.id,
+            cache_type: c.cache_type,
+            size: c.size.max(0) as u64,
+            shared: c.shared,
+            in_use: c.in_use,
+            created_at: c.created_at,
+            last_used_at: c.last_used_at,
+            usage_count: c.usage_count.max(0) as u64,
+            description: c.description,
+        }
+    }
+}
+
+/// A line of `docker buildx du --verbose` that couldn't be parsed, and why.
+pub type ParseError = (String, Box<dyn std::error::Error + Send + Sync>);
+
+/// The keys `docker buildx du --verbose` prints, for records and the totals after them.
+const DU_KEYS: &[&str] = &[
+    "ID",
+    "Parent",
+    "Parents",
+    "Created at",
+    "Mutable",
+    "Reclaimable",
+    "Shared",
+    "Private",
+    "Size",
+    "Description",
+    "Usage count",
+    "Last used",
+    "Type",
+    "Total",
+];
+
+/// Fields whose value can go on over the following lines, a description can have newlines in
+/// it and each of several parents is listed on a line of its own.
+const DU_MULTILINE_KEYS: &[&str] = &["Parents", "Description"];
+
+/// Parses `docker buildx du --verbose`, which prints each record as `Key: value` lines with a
+/// blank line between records. `Last used` is only roughly how long before `now`, see
+/// [`human_duration`]. Only the
+/// keys buildx prints are taken as fields, other lines carry on the value before them if that
+/// can span lines and are an error otherwise, rather than guessing at what they are.
+pub fn parse_buildx_du(output: &str, now: Timestamp) -> Result<Vec<BuildCache>, ParseError> {
+    let mut records = vec![];
+    let mut fields: BTreeMap<&str, (&str, String)> = BTreeMap::new();
+    let mut last_key = None;
+    // A blank line only ends a record if a field follows, in a description it's part of it
+    let mut blank = false;
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            blank = true;
+            continue;
+        }
+        let field = line
+            .split_once(':')
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .filter(|(key, _)| DU_KEYS.contains(key));
+        match (field, last_key) {
+            (Some((key, value)), _) => {
+                if blank || (key == "ID" && fields.contains_key("ID")) {
+                    // The totals at the end are a block without an ID
+                    if fields.contains_key("ID") {
+                        records.push(du_record(&fields, now)?);
+                    }
+                    fields.clear();
+                }
+                fields.insert(key, (line, value.to_string()));
+                last_key = Some(key);
+            }
+            (None, Some(key)) if DU_MULTILINE_KEYS.contains(&key) && !fields.is_empty() => {
+                let (_, value) = fields.get_mut(key).unwrap();
+                if !value.is_empty() {
+                    value.push_str(if blank { "\n\n" } else { "\n" });
+                }
+                value.push_str(line.trim());
+            }
+            (None, _) => return Err((line.to_string(), "not a field buildx du prints".into())),
+        }
+        blank = false;
+    }
+    if fields.contains_key("ID") {
+        records.push(du_record(&fields, now)?);
+    }
+    Ok(records)
+}
+
+fn du_record(
+    fields: &BTreeMap<&str, (&str, String)>,
+    now: Timestamp,
+) -> Result<BuildCache, ParseError> {
+    let value = |key| {
+        fields
+            .get(key)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    };
+    let invalid = |key, e: Box<dyn std::error::Error + Send + Sync>| {
+        let line = fields.get(key).map(|(line, _)| *line).unwrap_or_default();
+        (line.to_string(), e)
+    };
+    // Go's time.Time formatting, e.g. `2025-07-01 10:00:00.123456789 +0000 UTC`
+    let created_at = BrokenDownTime::parse_prefix("%Y-%m-%d %H:%M:%S%.f %z", value("Created at"))
+        .and_then(|(tm, _)| tm.to_timestamp())
+        .map_err(|e| invalid("Created at", e.into()))?;
+    let last_used_at = match value("Last used") {
+        "" => None,
+        last_used => {
+            let ago = last_used
+                .strip_suffix(" ago")
+                .and_then(human_duration)
+                .ok_or_else(|| invalid("Last used", "not a duration buildx prints".into()))?;
+            Some(
+                now.checked_sub(ago)
+                    .map_err(|e| invalid("Last used", e.into()))?,
+            )
+        }
+    };
+    let size = value("Size")
+        .parse::<human_size::Size>()
+        .map_err(|e| invalid("Size", e.into()))?;
+    let usage_count = match value("Usage count") {
+        "" => 0,
+        count => count
+            .parse()
+            .map_err(|e: std::num::ParseIntError| invalid("Usage count", e.into()))?,
+    };
+    Ok(BuildCache {
+        id: value("ID").to_string(),
+        cache_type: value("Type").to_string(),
+        size: size.to_bytes(),
+        shared: value("Shared") == "true",
+        // Records are reclaimable unless they're in use
+        in_use: value("Reclaimable") == "false",
+        created_at,
+        last_used_at,
+        usage_count,
+        description: value("Description").to_string(),
+    })
+}
+
+/// Parses how buildx says how long ago something was, like `3 hours` or `About a minute`. It
+/// rounds to the largest unit, so this is only as exact as that: `2 days` could be anything up
+/// to 3 days. Months are 30 days and years 365.
+fn human_duration(s: &str) -> Option<SignedDuration> {
+    let seconds = match s.to_lowercase().as_str() {
+        "less than a second" => 0,
+        "about a minute" => 60,
+        "about an hour" => 60 * 60,
+        s => {
+            let (count, unit) = s.split_once(' ')?;
+            let count = count.parse::<i64>().ok()?;
+            let unit = match unit.trim_end_matches('s') {
+                "second" => 1,
+                "minute" => 60,
+                "hour" => 60 * 60,
+                "day" => 24 * 60 * 60,
+                "week" => 7 * 24 * 60 * 60,
+                "month" => 30 * 24 * 60 * 60,
+                "year" => 365 * 24 * 60 * 60,
+                _ => return None,
+            };
+            count.checked_mul(unit)?
+        }
+    };
+    Some(SignedDuration::from_secs(seconds))
+}
+
+/// The name filters match the description.
+impl Record for BuildCacheRecord {
+    type Summary = BuildCacheSizeSummary;
+
+    fn query(filter: &FilterOptions) -> error::Result<(String, QueryArgs)> {
+        crate::check_filters(filter)?;
+
+        let mut query_str = "{BuildCache{".to_string();
+        query_str.push_str(&crate::created_edges(filter));
+        if let Some(used_before) = filter.resolve_used_before(&Zoned::now()) {
+            query_str.push_str(&format!(
+                "last_used_before(timestamp: \"{}\")\n",
+                used_before
+            ));
+        }
+
+        let mut query_args = QueryArgs::new();
+        let id_filter = crate::id_filters(filter, &mut query_args);
+        let size_filter = crate::size_filters(filter, &mut query_args);
+        let name_filter = crate::name_filters(filter, &mut query_args);
+
+        if filter.unused {
+            query_str.push_str(r#"in_use @filter(op: "=", value: ["$in_use"])"#);
+            query_str.push('\n');
+            query_args.insert("in_use".into(), false.into());
+        }
+
+        query_str.push_str(&format!(
+            "id @output\n{id_filter}description @output\n{name_filter}type @output\nsize @output\n{size_filter}shared @output\nin_use @output\ncreated @output\nlast_used @output\nusage_count @output\n"
+        ));
+
+        query_str.push_str("}}");
+        Ok((query_str, query_args))
+    }
+
+    fn from_row(row: Row) -> Self {
+        let id = row["id"].as_str().unwrap().to_string();
+        let name = match row["description"].as_str().unwrap() {
+            "" => id.clone(),
+            description => description.to_string(),
+        };
+        Self {
+            id,
+            name,
+            cache_type: row["type"].as_str().unwrap().to_string(),
+            size: row["size"].as_u64().unwrap(),
+            shared: row["shared"].as_bool().unwrap(),
+            in_use: row["in_use"].as_bool().unwrap(),
+            created: row["created"].as_str().unwrap().to_string(),
+            last_used: row["last_used"].as_str().map(|s| s.to_string()),
+            usage_count: row["usage_count"].as_u64().unwrap(),
+        }
+    }
+
+    /// Shared records' data is still used by something else, so pruning them frees nothing.
+    fn summarise(records: &[Self]) -> BuildCacheSizeSummary {
+        BuildCacheSizeSummary {
+            records: records.len(),
+            size: records.iter().map(|r| r.size).sum(),
+            reclaimable: records.iter().filter(|r| !r.shared).map(|r| r.size).sum(),
+        }
+    }
+
+    /// Records are pruned one at a time.
+    fn remove(backend: &dyn Backend, ids: &[&str]) -> Vec<error::Result<()>> {
+        ids.iter()
+            .map(|id| backend.remove_build_cache(id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_durations() {
+        let secs = |s| human_duration(s).map(|d| d.as_secs());
+        assert_eq!(secs("Less than a second"), Some(0));
+        assert_eq!(secs("1 second"), Some(1));
+        assert_eq!(secs("45 seconds"), Some(45));
+        assert_eq!(secs("About a minute"), Some(60));
+        assert_eq!(secs("2 minutes"), Some(120));
+        assert_eq!(secs("About an hour"), Some(3600));
+        assert_eq!(secs("3 weeks"), Some(21 * 24 * 3600));
+        assert_eq!(secs("2 months"), Some(60 * 24 * 3600));
+        assert_eq!(secs("2 years"), Some(730 * 24 * 3600));
+        assert_eq!(secs("48h0m0s"), None);
+        assert_eq!(secs("2 fortnights"), None);
+    }
+
+    #[test]
+    fn parses_buildx_du() {
+        // Laid out like buildx's tabwriter does, with go-units sizes and durations
+        let output = "ID:\t\tztj1ve0h2ml5qmlnk0lrwmt6t
+Created at:\t2025-07-01 10:00:00.123456789 +0000 UTC
+Mutable:\tfalse
+Reclaimable:\ttrue
+Shared:\t\tfalse
+Size:\t\t12.29MB
+Description:\t[builder 3/5] RUN cargo build --release
+Usage count:\t3
+Last used:\t2 days ago
+Type:\t\tregular
+
+ID:\t\tq1w2e3r4t5y6u7i8o9p0a1s2d
+Parent:\t\tztj1ve0h2ml5qmlnk0lrwmt6t
+Created at:\t2025-07-02 08:30:00.5 +0000 UTC
+Mutable:\ttrue
+Reclaimable:\tfalse
+Shared:\t\ttrue
+Size:\t\t0B
+Description:\tmount / from exec /bin/sh -c cargo build --release
+Usage count:\t1
+Type:\t\texec.cachemount
+
+ID:\t\tk9j8h7g6f5d4s3a2p1o0i9u8y
+Created at:\t2025-07-09 23:00:00 +0000 UTC
+Mutable:\tfalse
+Reclaimable:\ttrue
+Shared:\t\tfalse
+Size:\t\t4.096kB
+Usage count:\t2
+Last used:\tAbout an hour ago
+Type:\t\tsource.local
+
+Shared:\t\t0B
+Private:\t12.29MB
+Reclaimable:\t12.29MB
+Total:\t\t12.29MB
+";
+        let now: Timestamp = "2025-07-10T00:00:00Z".parse().unwrap();
+        let records = parse_buildx_du(output, now).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].id, "ztj1ve0h2ml5qmlnk0lrwmt6t");
+        assert_eq!(records[0].size, 12_290_000);
+        assert_eq!(records[0].usage_count, 3);
+        assert_eq!(
+            records[0].last_used_at,
+            Some("2025-07-08T00:00:00Z".parse().unwrap())
+        );
+        assert!(!records[0].in_use && !records[0].shared);
+        assert_eq!(records[1].cache_type, "exec.cachemount");
+        assert_eq!(records[1].last_used_at, None);
+        assert_eq!(records[1].last_active(), records[1].created_at);
+        assert!(records[1].in_use && records[1].shared);
+        assert_eq!(
+            records[2].last_used_at,
+            Some("2025-07-09T23:00:00Z".parse().unwrap())
+        );
+
+        let (line, _) = parse_buildx_du(
+            "ID: x\nCreated at: 2025-07-01 10:00:00 +0000 UTC\nSize: 0B\nLast used: 48h0m0s ago\n",
+            now,
+        )
+        .unwrap_err();
+        assert_eq!(line, "Last used: 48h0m0s ago");
+
+        let (line, _) = parse_buildx_du("ID: x\nCreated at: yesterday\n", now).unwrap_err();
+        assert_eq!(line, "Created at: yesterday");
+
+        // Descriptions and parents can go over several lines, even with colons and blank lines
+        let output = "ID:\t\tztj1ve0h2ml5qmlnk0lrwmt6t
+Parents:
+\t- q1w2e3r4t5y6u7i8o9p0a1s2d
+\t- a1s2d3f4g5h6j7k8l9z0x1c2v
+Created at:\t2025-07-01 10:00:00 +0000 UTC
+Mutable:\tfalse
+Reclaimable:\ttrue
+Shared:\t\tfalse
+Size:\t\t1kB
+Description:\t[builder 2/5] RUN <<EOF
+set -e
+echo note: done
+
+EOF
+Usage count:\t1
+Type:\t\tregular
+
+Reclaimable:\t1kB
+Total:\t\t1kB
+";
+        let records = parse_buildx_du(output, now).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].description,
+            "[builder 2/5] RUN <<EOF\nset -e\necho note: done\n\nEOF"
+        );
+        assert_eq!(records[0].size, 1000);
+        assert_eq!(records[0].cache_type, "regular");
+
+        // Anything else is refused rather than guessed at
+        let (line, _) = parse_buildx_du("ID: x\nType: regular\nColour: blue\n", now).unwrap_err();
+        assert_eq!(line, "Colour: blue");
+    }
+}
@@ -0,0 +1,111 @@
+//! Asking before `rm` deletes anything.
+use crate::output::{Removable, human_size, size_or_unknown};
+use std::io::{self, BufRead, Write};
+
+/// Shows what's about to be removed with what removing it frees and asks whether to remove all
+/// of it, none of it or to pick one at a time. Returns what the user agreed to remove.
+pub fn confirm<T: Removable>(
+    items: Vec<T>,
+    reclaimable: u64,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<Vec<T>> {
+    if items.is_empty() {
+        return Ok(items);
+    }
+    let names = items
+        .iter()
+        .map(|i| i.names().join(", "))
+        .collect::<Vec<_>>();
+    let max_name_len = names.iter().map(|n| n.len()).max().unwrap_or_default();
+    for (item, name) in items.iter().zip(&names) {
+        writeln!(
+            out,
+            "{:width$}\t{}",
+            name,
+            size_or_unknown(item.size()),
+            width = max_name_len
+        )?;
+    }
+    let question = format!(
+        "Remove {} {}s reclaiming {}? [a]ll/[n]one/[p]ick",
+        items.len(),
+        T::KIND,
+        human_size(reclaimable)
+    );
+    loop {
+        match ask(&question, input, out)?.as_str() {
+            "a" | "all" | "y" | "yes" => return Ok(items),
+            "" | "n" | "none" | "no" => return Ok(vec![]),
+            "p" | "pick" => break,
+            _ => continue,
+        }
+    }
+
+    let mut picked = vec![];
+    for item in items {
+        let question = format!(
+            "Remove {} ({})? [y/N]",
+            item.names().join(", "),
+            size_or_unknown(item.size())
+        );
+        if matches!(ask(&question, input, out)?.as_str(), "y" | "yes") {
+            picked.push(item);
+        }
+    }
+    Ok(picked)
+}
+
+/// Reading nothing (end of input) counts as an empty answer.
+fn ask(question: &str, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<String> {
+    write!(out, "{question} ")?;
+    out.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::ImageRecord;
+
+    fn images() -> Vec<ImageRecord> {
+        ["ubuntu:18.04", "work/app:1", "alpine:3"]
+            .into_iter()
+            .map(|name| ImageRecord {
+                id: name.to_string(),
+                name: name.to_string(),
+                registry: None,
+                namespace: None,
+                repo: None,
+                tag: None,
+                size: 1_000_000,
+                created: String::new(),
+                dangling: false,
+                labels: vec![],
+                parent: None,
+                references: vec![name.to_string()],
+                untag: None,
+            })
+            .collect()
+    }
+
+    fn answer(input: &str) -> Vec<String> {
+        let mut out = vec![];
+        confirm(images(), 2_000_000, &mut input.as_bytes(), &mut out)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.name)
+            .collect()
+    }
+
+    #[test]
+    fn all_none_or_pick() {
+        assert_eq!(answer("a\n").len(), 3);
+        assert!(answer("n\n").is_empty());
+        assert!(answer("").is_empty());
+        assert_eq!(answer("what\nall\n").len(), 3);
+        assert_eq!(answer("p\ny\nn\nyes\n"), ["ubuntu:18.04", "alpine:3"]);
+    }
+}
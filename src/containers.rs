@@ -0,0 +1,260 @@
+use crate::args::FilterOptions;
+use crate::backend::Backend;
+use crate::error::{Error, Result};
+use crate::output::{ContainerRecord, ContainerSizeSummary, Row};
+use crate::reference::Reference;
+use crate::{Image, QueryArgs, Record, docker, engine, podman};
+use jiff::Timestamp;
+use serde::Deserialize;
+use trustfall::FieldValue;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ContainerOutput {
+    Podman(podman::Container),
+    Docker(docker::Container),
+}
+
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct Container {
+    pub id: String,
+    pub name: String,
+    /// The image reference the container was created from, as the runtime reports it
+    pub image: String,
+    /// Only podman tells us the resolved image ID, docker just gives the reference
+    pub image_id: Option<String>,
+    pub state: String,
+    pub status: String,
+    pub created_at: Timestamp,
+    /// Names of the networks the container is attached to
+    pub networks: Vec<String>,
+}
+
+/// States of containers that haven't stopped.
+pub const RUNNING_STATES: &[&str] = &["running", "paused", "restarting", "removing"];
+
+/// What `container inspect` adds to the listing, which takes longer to get so it's only
+/// looked up when needed.
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct ContainerDetails {
+    pub id: String,
+    /// `None` if the container hasn't exited
+    pub exit_code: Option<i64>,
+    pub finished_at: Option<Timestamp>,
+    /// Bytes in the container's writable layer
+    pub size: Option<u64>,
+}
+
+impl From<docker::ContainerInspect> for ContainerDetails {
+    fn from(c: docker::ContainerInspect) -> Self {
+        // Containers that never stopped have a zero finish time
+        let finished_at = c.state.finished_at.filter(|t| *t > Timestamp::UNIX_EPOCH);
+        let exited = finished_at.is_some() && !RUNNING_STATES.contains(&c.state.status.as_str());
+        Self {
+            id: c.id,
+            exit_code: exited.then_some(c.state.exit_code),
+            finished_at: finished_at.filter(|_| exited),
+            size: c.size_rw.and_then(|s| u64::try_from(s).ok()),
+        }
+    }
+}
+
+impl Container {
+    pub fn is_running(&self) -> bool {
+        RUNNING_STATES.contains(&self.state.as_str())
+    }
+
+    /// Whether this container was created from the given image, either by ID or by name.
+    pub fn uses_image(&self, image: &Image) -> bool {
+        if let Some(id) = &self.image_id
+            && same_id(id, &image.hash)
+        {
+            return true;
+        }
+        if same_id(&self.image, &image.hash) {
+            return true;
+        }
+        // docker says `ubuntu` where podman says `docker.io/library/ubuntu`
+        let (Some(used), Some(named)) = (Reference::parse(&self.image), image.reference()) else {
+            return false;
+        };
+        if let Some(digest) = &used.digest {
+            return used.name() == named.name()
+                && (image.digest.as_ref() == Some(digest)
+                    || image
+                        .repo_digests
+                        .iter()
+                        .any(|d| d.ends_with(digest.as_str())));
+        }
+        used.name() == named.name() && used.tag_or_latest() == named.tag_or_latest()
+    }
+}
+
+/// Image IDs get printed both in full and truncated to 12 characters, with and without the
+/// `sha256:` prefix, so treat them as equal if one is a prefix of the other.
+pub(crate) fn same_id(a: &str, b: &str) -> bool {
+    let a = a.trim_start_matches("sha256:");
+    let b = b.trim_start_matches("sha256:");
+    if a.len() < 12 || b.len() < 12 || !a.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return false;
+    }
+    a.starts_with(b) || b.starts_with(a)
+}
+
+impl From<ContainerOutput> for Container {
+    fn from(x: ContainerOutput) -> Self {
+        match x {
+            ContainerOutput::Podman(p) => p.into(),
+            ContainerOutput::Docker(d) => d.into(),
+        }
+    }
+}
+
+impl From<podman::Container> for Container {
+    fn from(c: podman::Container) -> Self {
+        Self {
+            id: c.id,
+            name: c.names.first().cloned().unwrap_or_default(),
+            image: c.image,
+            image_id: Some(c.image_id).filter(|x| !x.is_empty()),
+            state: c.state,
+            status: c.status,
+            created_at: Timestamp::from_second(c.created).unwrap_or_default(),
+            networks: c.networks.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<docker::Container> for Container {
+    fn from(c: docker::Container) -> Self {
+        let name = c.names.split(',').next().unwrap_or_default().to_string();
+        Self {
+            id: c.id,
+            name,
+            image: c.image,
+            image_id: None,
+            state: c.state,
+            status: c.status,
+            created_at: c.created_at,
+            networks: c
+                .networks
+                .split(',')
+                .filter(|n| !n.is_empty())
+                .map(|n| n.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl From<engine::Container> for Container {
+    fn from(c: engine::Container) -> Self {
+        let name = c
+            .names
+            .first()
+            .map(|n| n.trim_start_matches('/').to_string())
+            .unwrap_or_default();
+        Self {
+            id: c.id,
+            name,
+            image: c.image,
+            image_id: Some(c.image_id).filter(|x| !x.is_empty()),
+            state: c.state,
+            status: c.status,
+            created_at: Timestamp::from_second(c.created).unwrap_or_default(),
+            networks: c
+                .network_settings
+                .and_then(|s| s.networks)
+                .map(|n| n.into_keys().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Only stopped containers are queried. `--image` is matched part by part, so `ubuntu` is
+/// `docker.io/library/ubuntu` and without a tag it matches every tag.
+impl Record for ContainerRecord {
+    type Summary = ContainerSizeSummary;
+
+    fn query(filter: &FilterOptions) -> Result<(String, QueryArgs)> {
+        crate::check_filters(filter)?;
+
+        let mut query_str = "{Container{".to_string();
+        query_str.push_str(&crate::created_edges(filter));
+        if let Some(exited_before) = filter.exited_before {
+            query_str.push_str(&format!(
+                "exited_before(timestamp: \"{}\")\n",
+                exited_before
+            ));
+        }
+
+        let mut query_args = QueryArgs::new();
+        let id_filter = crate::id_filters(filter, &mut query_args);
+        let size_filter = crate::size_filters(filter, &mut query_args);
+        let name_filter = crate::name_filters(filter, &mut query_args);
+
+        query_str.push_str(r#"state @filter(op: "not_one_of", value: ["$running"])"#);
+        query_str.push('\n');
+        query_args.insert("running".into(), RUNNING_STATES.into());
+
+        if !filter.exit_code.is_empty() {
+            query_str.push_str(r#"exit_code @filter(op: "one_of", value: ["$exit_codes"])"#);
+            query_str.push('\n');
+            query_args.insert("exit_codes".into(), filter.exit_code.as_slice().into());
+        }
+
+        if let Some(image) = &filter.image {
+            let reference = Reference::parse(image).ok_or_else(|| {
+                Error::InvalidQuery(format!("'{image}' isn't an image reference"))
+            })?;
+            let mut parts = vec![
+                ("registry", Some(reference.registry)),
+                ("namespace", Some(reference.namespace)),
+                ("repo", Some(reference.repository)),
+                ("tag", reference.tag),
+                ("digest", reference.digest),
+            ];
+            parts.retain(|(_, value)| value.is_some());
+            query_str.push_str("image {\n");
+            for (property, value) in parts {
+                let var = format!("image_{property}");
+                query_str.push_str(&format!(
+                    "{property} @filter(op: \"=\", value: [\"${var}\"])\n"
+                ));
+                query_args.insert(var.into(), value.into());
+            }
+            query_str.push_str("}\n");
+        }
+
+        query_str.push_str(&format!(
+            "id @output\n{id_filter}name @output\n{name_filter}image_name @output\nstate @output\ncreated @output\nexit_code @output\nfinished @output\nsize @output\n{size_filter}"
+        ));
+
+        query_str.push_str("}}");
+        Ok((query_str, query_args))
+    }
+
+    fn from_row(row: Row) -> Self {
+        let as_string = |v: &FieldValue| v.as_str().unwrap().to_string();
+        Self {
+            id: as_string(&row["id"]),
+            name: as_string(&row["name"]),
+            image: as_string(&row["image_name"]),
+            state: as_string(&row["state"]),
+            created: as_string(&row["created"]),
+            exit_code: row["exit_code"].as_i64(),
+            finished: row["finished"].as_str().map(|s| s.to_string()),
+            size: row["size"].as_u64(),
+        }
+    }
+
+    fn summarise(containers: &[Self]) -> ContainerSizeSummary {
+        ContainerSizeSummary {
+            containers: containers.len(),
+            size: containers.iter().filter_map(|c| c.size).sum(),
+        }
+    }
+
+    fn remove(backend: &dyn Backend, ids: &[&str]) -> Vec<Result<()>> {
+        backend.remove_containers(ids)
+    }
+}
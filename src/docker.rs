@@ -1,5 +1,6 @@
 use jiff::{Timestamp, fmt::strtime::BrokenDownTime};
 use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -8,8 +9,13 @@ pub struct Image {
     pub created_at: Timestamp,
     #[serde(alias = "ID")]
     pub id: String,
+    #[serde(default)]
+    pub digest: String,
     pub repository: Option<String>,
     pub size: human_size::Size,
+    /// `N/A` unless docker has calculated it
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    pub shared_size: Option<u64>,
     pub tag: Option<String>,
 }
 
@@ -23,3 +29,134 @@ where
 
     tm.to_timestamp().map_err(serde::de::Error::custom)
 }
+
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Container {
+    #[serde(deserialize_with = "deserialize_docker_timestamp")]
+    pub created_at: Timestamp,
+    #[serde(alias = "ID")]
+    pub id: String,
+    pub image: String,
+    pub names: String,
+    pub state: String,
+    pub status: String,
+    /// Comma separated network names
+    #[serde(default)]
+    pub networks: String,
+}
+
+/// The part of `container inspect --size` we use, podman and the Engine API print the same.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerInspect {
+    #[serde(alias = "ID")]
+    pub id: String,
+    #[serde(default)]
+    pub state: ContainerState,
+    #[serde(default)]
+    pub size_rw: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerState {
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub exit_code: i64,
+    #[serde(default)]
+    pub finished_at: Option<Timestamp>,
+}
+
+/// An entry of `docker network inspect`, the Engine API lists networks in the same shape.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Network {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub driver: String,
+    #[serde(default)]
+    pub scope: String,
+    #[serde(default)]
+    pub created: Option<Timestamp>,
+    #[serde(default)]
+    pub internal: bool,
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+}
+
+/// An entry of `volume inspect`, which podman prints in the same shape.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Volume {
+    pub name: String,
+    #[serde(default)]
+    pub driver: String,
+    #[serde(default)]
+    pub mountpoint: String,
+    #[serde(default)]
+    pub created_at: Option<Timestamp>,
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+}
+
+/// The part of `docker image inspect` we use, podman prints the same shape.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageInspect {
+    #[serde(alias = "ID", default)]
+    pub id: String,
+    #[serde(rename = "RootFS", default)]
+    pub root_fs: RootFs,
+    #[serde(default)]
+    pub config: Option<ImageConfig>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageConfig {
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RootFs {
+    #[serde(default)]
+    pub layers: Vec<String>,
+}
+
+/// A line of `docker image history --human=false --format json`.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct History {
+    #[serde(deserialize_with = "deserialize_bytes")]
+    pub size: u64,
+}
+
+fn deserialize_optional_size<'de, D>(d: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    Ok(s.parse::<human_size::Size>()
+        .ok()
+        .map(|size| size.to_bytes()))
+}
+
+/// Sizes are printed as a string, in bytes with `--human=false`.
+fn deserialize_bytes<'de, D>(d: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    match s.parse() {
+        Ok(bytes) => Ok(bytes),
+        Err(_) => s
+            .parse::<human_size::Size>()
+            .map(|size| size.to_bytes())
+            .map_err(serde::de::Error::custom),
+    }
+}
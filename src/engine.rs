@@ -0,0 +1,772 @@
+//! A small client for the Docker Engine API spoken over a unix socket. Podman also serves this
+//! API (`podman system service`) so it's used in preference to scraping the CLI output.
+use crate::error::{Error, Result};
+use jiff::Timestamp;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// How long to wait on the daemon before giving up. Deleting a big image can take a while, this
+/// is only so a daemon that's stopped responding doesn't hang us forever.
+const TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Image {
+    pub id: String,
+    #[serde(default)]
+    pub parent_id: String,
+    #[serde(default)]
+    pub repo_tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub repo_digests: Option<Vec<String>>,
+    pub created: i64,
+    pub size: i64,
+    #[serde(default)]
+    pub shared_size: i64,
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    pub containers: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Container {
+    pub id: String,
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub image: String,
+    #[serde(rename = "ImageID", default)]
+    pub image_id: String,
+    pub created: i64,
+    pub state: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub network_settings: Option<NetworkSettings>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NetworkSettings {
+    /// Keyed by network name, the endpoint details aren't needed
+    #[serde(default)]
+    pub networks: Option<BTreeMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Version {
+    pub version: String,
+    #[serde(default)]
+    pub platform: Platform,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Platform {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageInspect {
+    #[serde(rename = "RootFS", default)]
+    pub root_fs: RootFs,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RootFs {
+    #[serde(default)]
+    pub layers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct History {
+    pub size: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Volume {
+    pub name: String,
+    #[serde(default)]
+    pub driver: String,
+    #[serde(default)]
+    pub mountpoint: String,
+    #[serde(default)]
+    pub created_at: Option<Timestamp>,
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+    /// Only filled in by `/system/df`
+    #[serde(default)]
+    pub usage_data: Option<UsageData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UsageData {
+    pub size: i64,
+    pub ref_count: i64,
+}
+
+impl Default for UsageData {
+    fn default() -> Self {
+        Self {
+            size: -1,
+            ref_count: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BuildCache {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "Type", default)]
+    pub cache_type: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub in_use: bool,
+    #[serde(default)]
+    pub shared: bool,
+    pub size: i64,
+    pub created_at: Timestamp,
+    #[serde(default)]
+    pub last_used_at: Option<Timestamp>,
+    #[serde(default)]
+    pub usage_count: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SystemDf {
+    #[serde(default)]
+    volumes: Option<Vec<Volume>>,
+    #[serde(default)]
+    build_cache: Option<Vec<BuildCache>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BuildPrune {
+    #[serde(default)]
+    caches_deleted: Option<Vec<String>>,
+}
+
+/// One line of what deleting an image did, either untagging a name or deleting an image (or
+/// one of its layers).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ImageDeleted {
+    #[serde(default)]
+    deleted: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorMessage {
+    message: String,
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    body: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    socket: PathBuf,
+    /// For each read or write on the socket
+    timeout: Duration,
+}
+
+impl Client {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+            timeout: TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Finds the socket from `DOCKER_HOST` or the default location. Only `unix://` hosts are
+    /// supported, anything else returns `None` so the caller can fall back to the CLI.
+    pub fn from_env() -> Option<Self> {
+        let socket = match std::env::var("DOCKER_HOST") {
+            Ok(host) if !host.is_empty() => PathBuf::from(host.strip_prefix("unix://")?),
+            _ => PathBuf::from(DEFAULT_SOCKET),
+        };
+        if socket.exists() {
+            Some(Self::new(socket))
+        } else {
+            None
+        }
+    }
+
+    /// Like [`Client::from_env`] but also checks the daemon is listening on the socket.
+    pub fn connect() -> Option<Self> {
+        Self::from_env().filter(|client| client.ping().is_ok())
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    pub fn ping(&self) -> Result<()> {
+        self.request("GET", "/_ping").map(|_| ())
+    }
+
+    pub fn version(&self) -> Result<Version> {
+        self.get_json("/version")
+    }
+
+    pub fn images(&self) -> Result<Vec<Image>> {
+        self.get_json("/images/json?shared-size=1")
+    }
+
+    pub fn containers(&self) -> Result<Vec<Container>> {
+        self.get_json("/containers/json?all=true")
+    }
+
+    /// Inspects a container, including the size of its writable layer.
+    pub fn inspect_container(&self, id: &str) -> Result<crate::docker::ContainerInspect> {
+        self.get_json(&format!("/containers/{}/json?size=true", path_segment(id)))
+    }
+
+    /// Docker lists networks the same way `docker network inspect` prints them.
+    pub fn networks(&self) -> Result<Vec<crate::docker::Network>> {
+        self.get_json("/networks")
+    }
+
+    pub fn inspect_image(&self, id: &str) -> Result<ImageInspect> {
+        self.get_json(&format!("/images/{}/json", path_segment(id)))
+    }
+
+    /// The build steps of the image, newest first.
+    pub fn image_history(&self, id: &str) -> Result<Vec<History>> {
+        self.get_json(&format!("/images/{}/history", path_segment(id)))
+    }
+
+    /// Volumes with how much they hold and how many containers use them, which listing them
+    /// doesn't say.
+    pub fn volumes(&self) -> Result<Vec<Volume>> {
+        let df: SystemDf = self.get_json("/system/df?type=volume")?;
+        Ok(df.volumes.unwrap_or_default())
+    }
+
+    pub fn build_cache(&self) -> Result<Vec<BuildCache>> {
+        let df: SystemDf = self.get_json("/system/df?type=build-cache")?;
+        Ok(df.build_cache.unwrap_or_default())
+    }
+
+    /// Prunes the build cache record with this ID. The daemon doesn't complain if it can't,
+    /// say because the record is in use, it just doesn't list it as deleted.
+    pub fn prune_build_cache(&self, id: &str) -> Result<()> {
+        let filters = percent_encode(&format!(r#"{{"id":["{id}"]}}"#));
+        let pruned: BuildPrune = self.json("POST", &format!("/build/prune?filters={filters}"))?;
+        if pruned.caches_deleted.unwrap_or_default().is_empty() {
+            Err(Error::Runtime(format!(
+                "build cache record {id} wasn't pruned, it may be in use"
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Removes an image by name or ID, returning whether the image was deleted rather than only
+    /// untagged.
+    pub fn remove_image(&self, reference: &str) -> Result<bool> {
+        let path = format!("/images/{}", path_segment(reference));
+        self.request("DELETE", &path)
+            .and_then(|response| image_deleted(&path, &response))
+    }
+
+    /// Removes each image in turn over a single connection, returning the result for each.
+    pub fn remove_images(&self, references: &[&str]) -> Vec<Result<bool>> {
+        references
+            .iter()
+            .zip(self.delete_all("images", references))
+            .map(|(reference, response)| {
+                let path = format!("/images/{}", path_segment(reference));
+                response.and_then(|response| image_deleted(&path, &response))
+            })
+            .collect()
+    }
+
+    pub fn remove_container(&self, id: &str) -> Result<()> {
+        self.request("DELETE", &format!("/containers/{}", path_segment(id)))
+            .map(|_| ())
+    }
+
+    /// Like [`Client::remove_images`] but for containers.
+    pub fn remove_containers(&self, ids: &[&str]) -> Vec<Result<()>> {
+        self.delete_all("containers", ids)
+            .into_iter()
+            .map(|r| r.map(|_| ()))
+            .collect()
+    }
+
+    pub fn remove_volume(&self, name: &str) -> Result<()> {
+        self.request("DELETE", &format!("/volumes/{}", path_segment(name)))
+            .map(|_| ())
+    }
+
+    /// Like [`Client::remove_images`] but for volumes.
+    pub fn remove_volumes(&self, names: &[&str]) -> Vec<Result<()>> {
+        self.delete_all("volumes", names)
+            .into_iter()
+            .map(|r| r.map(|_| ()))
+            .collect()
+    }
+
+    pub fn remove_network(&self, id: &str) -> Result<()> {
+        self.request("DELETE", &format!("/networks/{}", path_segment(id)))
+            .map(|_| ())
+    }
+
+    /// Like [`Client::remove_images`] but for networks.
+    pub fn remove_networks(&self, ids: &[&str]) -> Vec<Result<()>> {
+        self.delete_all("networks", ids)
+            .into_iter()
+            .map(|r| r.map(|_| ()))
+            .collect()
+    }
+
+    /// Deletes `/{collection}/{name}` for each name over a single connection.
+    fn delete_all(&self, collection: &str, references: &[&str]) -> Vec<Result<Response>> {
+        let mut connection = match self.open() {
+            Ok(connection) => connection,
+            Err(e) => {
+                let message = e.to_string();
+                return references
+                    .iter()
+                    .map(|_| Err(Error::Runtime(message.clone())))
+                    .collect();
+            }
+        };
+        let last = references.len().saturating_sub(1);
+        references
+            .iter()
+            .enumerate()
+            .map(|(i, reference)| {
+                let path = format!("/{collection}/{}", path_segment(reference));
+                self.exchange(&mut connection, "DELETE", &path, i < last)
+            })
+            .collect()
+    }
+
+    fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.json("GET", path)
+    }
+
+    fn json<T: DeserializeOwned>(&self, method: &str, path: &str) -> Result<T> {
+        let response = self.request(method, path)?;
+        serde_json::from_slice(&response.body).map_err(|source| {
+            let body = String::from_utf8_lossy(&response.body);
+            Error::UnparsableOutput {
+                command: format!("{method} {path}"),
+                line: body.chars().take(200).collect(),
+                source: source.into(),
+            }
+        })
+    }
+
+    fn request(&self, method: &str, path: &str) -> Result<Response> {
+        let mut connection = self.open()?;
+        self.exchange(&mut connection, method, path, false)
+    }
+
+    fn unreachable(&self, e: io::Error) -> Error {
+        Error::DaemonUnreachable {
+            runtime: format!("engine API at {}", self.socket.display()),
+            message: e.to_string(),
+        }
+    }
+
+    fn open(&self) -> Result<BufReader<UnixStream>> {
+        let stream = UnixStream::connect(&self.socket).map_err(|e| self.unreachable(e))?;
+        stream
+            .set_read_timeout(Some(self.timeout))
+            .and_then(|()| stream.set_write_timeout(Some(self.timeout)))
+            .map_err(|e| self.unreachable(e))?;
+        Ok(BufReader::new(stream))
+    }
+
+    /// Sends one request and reads its response, leaving the connection open for another
+    /// request if `keep_alive` is set.
+    fn exchange(
+        &self,
+        connection: &mut BufReader<UnixStream>,
+        method: &str,
+        path: &str,
+        keep_alive: bool,
+    ) -> Result<Response> {
+        let header = if keep_alive { "keep-alive" } else { "close" };
+        let request =
+            format!("{method} {path} HTTP/1.1\r\nHost: docker\r\nConnection: {header}\r\n\r\n");
+        connection
+            .get_mut()
+            .write_all(request.as_bytes())
+            .map_err(|e| self.unreachable(e))?;
+        let response = read_response(connection).map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                self.unreachable(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no response to {method} {path} within {:?}", self.timeout),
+                ))
+            }
+            _ => e.into(),
+        })?;
+        if (200..300).contains(&response.status) {
+            Ok(response)
+        } else {
+            let message = serde_json::from_slice::<ErrorMessage>(&response.body)
+                .map(|e| e.message)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&response.body).into_owned());
+            Err(Error::Runtime(message.trim().to_string()))
+        }
+    }
+}
+
+/// Whether the response to deleting an image lists the image as deleted, rather than just
+/// untagged. A few old daemons reply with an empty body, which says nothing either way.
+fn image_deleted(path: &str, response: &Response) -> Result<bool> {
+    if response.body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(false);
+    }
+    let items: Vec<ImageDeleted> =
+        serde_json::from_slice(&response.body).map_err(|source| Error::UnparsableOutput {
+            command: format!("DELETE {path}"),
+            line: String::from_utf8_lossy(&response.body)
+                .chars()
+                .take(200)
+                .collect(),
+            source: source.into(),
+        })?;
+    Ok(items.iter().any(|i| i.deleted.is_some()))
+}
+
+/// Escapes everything but the unreserved characters for use in a query string.
+fn percent_encode(s: &str) -> String {
+    percent_encode_except(s, b"")
+}
+
+/// Escapes a name or ID for the request path. Like docker's own clients `/` and `:` are left
+/// as they are, repository names have them and the daemon takes the rest of the path as the
+/// name.
+fn path_segment(s: &str) -> String {
+    percent_encode_except(s, b"/:")
+}
+
+fn percent_encode_except(s: &str, safe: &[u8]) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b if safe.contains(&b) => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Reads a response off the connection, stopping at the end of its body so another can follow.
+/// A response without a length or chunking runs to the end of the connection.
+fn read_response(reader: &mut impl BufRead) -> io::Result<Response> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid("invalid HTTP status line"))?;
+
+    let mut chunked = false;
+    let mut length = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("incomplete HTTP response"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            } else if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse::<usize>().ok();
+            }
+        }
+    }
+
+    // These never have a body (RFC 9112 section 6.3), the daemon sends 204 without a length
+    let body = if (100..200).contains(&status) || status == 204 || status == 304 {
+        vec![]
+    } else if chunked {
+        read_chunked(reader)?
+    } else if let Some(length) = length {
+        let mut body = vec![0; length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|_| invalid("truncated HTTP body"))?;
+        body
+    } else {
+        let mut body = vec![];
+        reader.read_to_end(&mut body)?;
+        body
+    };
+    Ok(Response { status, body })
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut decoded = vec![];
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line
+            .split(';')
+            .next()
+            .and_then(|s| usize::from_str_radix(s.trim(), 16).ok())
+            .ok_or_else(|| invalid("invalid chunk size"))?;
+        if size == 0 {
+            // Skip any trailers up to the blank line ending the body
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                    return Ok(decoded);
+                }
+            }
+        }
+        let start = decoded.len();
+        decoded.resize(start + size, 0);
+        reader
+            .read_exact(&mut decoded[start..])
+            .map_err(|_| invalid("truncated chunk"))?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::images;
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    /// Serves each of the canned responses to one connection and returns the requests seen.
+    fn fake_daemon(
+        name: &str,
+        responses: Vec<String>,
+    ) -> (Client, thread::JoinHandle<Vec<String>>) {
+        fake_daemon_with(name, responses.into_iter().map(|r| vec![r]).collect())
+    }
+
+    /// Like [`fake_daemon`] but serves a list of responses to each connection in turn.
+    fn fake_daemon_with(
+        name: &str,
+        connections: Vec<Vec<String>>,
+    ) -> (Client, thread::JoinHandle<Vec<String>>) {
+        let path =
+            std::env::temp_dir().join(format!("docker-cleanup-{}-{name}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for responses in connections {
+                let (mut stream, _) = listener.accept().unwrap();
+                for response in responses {
+                    let mut buf = [0u8; 4096];
+                    let n = stream.read(&mut buf).unwrap();
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    requests.push(request.lines().next().unwrap_or_default().to_string());
+                    stream.write_all(response.as_bytes()).unwrap();
+                }
+            }
+            requests
+        });
+        (Client::new(path), handle)
+    }
+
+    #[test]
+    fn lists_images_over_socket() {
+        let body = r#"[{"Containers":0,"Created":1750414636,"Id":"sha256:e9d2252ab371a1149d3ef64b7793a274375dee5d9ec61b9e4fb41d75f156c1a1","Labels":{"org.opencontainers.image.version":"24.10"},"ParentId":"","RepoDigests":["ubuntu@sha256:cdf755952ed117f6126ff4e65810bf93767d4c38f5c7185b50ec1f1078b464cc"],"RepoTags":["localhost:5000/ubuntu:24.10"],"SharedSize":-1,"Size":82756709}]"#;
+        // Split the body over two chunks to exercise the chunked decoding
+        let (a, b) = body.split_at(40);
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{a}\r\n{:x}\r\n{b}\r\n0\r\n\r\n",
+            a.len(),
+            b.len()
+        );
+        let (client, server) = fake_daemon("images", vec![response]);
+
+        let images = client.images().unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            ["GET /images/json?shared-size=1 HTTP/1.1"]
+        );
+        assert_eq!(images.len(), 1);
+        let image: images::Image = images[0].clone().into();
+        assert_eq!(image.size, 82756709);
+        assert_eq!(image.repository, "localhost:5000/ubuntu");
+        assert_eq!(image.tag, "24.10");
+    }
+
+    #[test]
+    fn lists_volumes_with_their_usage() {
+        let body = r#"{"Volumes":[{"CreatedAt":"2025-06-30T08:00:00Z","Driver":"local","Labels":null,"Mountpoint":"/var/lib/docker/volumes/pgdata/_data","Name":"pgdata","Options":null,"Scope":"local","UsageData":{"RefCount":1,"Size":2048}},{"CreatedAt":"2025-06-30T09:00:00Z","Driver":"local","Labels":{"com.docker.compose.project":"ci"},"Mountpoint":"/var/lib/docker/volumes/ci_cache/_data","Name":"ci_cache","Scope":"local","UsageData":{"RefCount":0,"Size":-1}}]}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let (client, server) = fake_daemon("volumes", vec![response]);
+
+        let volumes = client.volumes().unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            ["GET /system/df?type=volume HTTP/1.1"]
+        );
+        let volumes = volumes
+            .into_iter()
+            .map(crate::Volume::from)
+            .collect::<Vec<_>>();
+        assert_eq!(volumes[0].size, Some(2048));
+        assert!(volumes[0].in_use);
+        assert_eq!(volumes[1].size, None);
+        assert!(!volumes[1].in_use);
+        assert_eq!(volumes[1].labels["com.docker.compose.project"], "ci");
+    }
+
+    #[test]
+    fn surfaces_daemon_errors() {
+        let body = r#"{"message":"conflict: unable to delete e9d2252ab371 (cannot be forced) - image is being used by running container 3f4e0c1d2b6a"}"#;
+        let response = format!(
+            "HTTP/1.1 409 Conflict\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let (client, server) = fake_daemon("rm", vec![response]);
+
+        let err = client.remove_image("ubuntu:24.10").unwrap_err();
+        assert_eq!(
+            server.join().unwrap(),
+            ["DELETE /images/ubuntu:24.10 HTTP/1.1"]
+        );
+        assert!(err.to_string().contains("image is being used"));
+    }
+
+    #[test]
+    fn removes_volumes_with_bare_no_content_responses() {
+        // No length and the connection kept open, so the body has to be known to be empty
+        let no_content = "HTTP/1.1 204 No Content\r\n\r\n".to_string();
+        let (client, server) = fake_daemon_with("bare", vec![vec![no_content; 3]]);
+        let client = client.with_timeout(Duration::from_secs(5));
+
+        let results = client.remove_volumes(&["ci_cache", "ci_build", "pgdata"]);
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "DELETE /volumes/ci_cache HTTP/1.1",
+                "DELETE /volumes/ci_build HTTP/1.1",
+                "DELETE /volumes/pgdata HTTP/1.1"
+            ]
+        );
+        assert!(results.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn names_are_escaped_in_the_path() {
+        let response = "HTTP/1.1 204 No Content\r\n\r\n".to_string();
+        let (client, server) = fake_daemon("escape", vec![response.clone(), response]);
+
+        client.remove_volume("ci cache?force=1").unwrap();
+        let deleted = client.remove_image(
+            "work/app@sha256:cdf755952ed117f6126ff4e65810bf93767d4c38f5c7185b50ec1f1078b464cc",
+        );
+        assert!(!deleted.unwrap());
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "DELETE /volumes/ci%20cache%3Fforce%3D1 HTTP/1.1",
+                "DELETE /images/work/app%40sha256:cdf755952ed117f6126ff4e65810bf93767d4c38f5c7185b50ec1f1078b464cc HTTP/1.1"
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_up_on_a_daemon_that_doesnt_answer() {
+        let path =
+            std::env::temp_dir().join(format!("docker-cleanup-{}-silent.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            thread::sleep(Duration::from_millis(500));
+            drop(stream);
+        });
+
+        let client = Client::new(path).with_timeout(Duration::from_millis(50));
+        let err = client.ping().unwrap_err();
+        assert!(matches!(err, Error::DaemonUnreachable { .. }));
+        assert!(err.to_string().contains("no response to GET /_ping"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn removes_images_over_one_connection() {
+        let deleted = r#"[{"Untagged":"ubuntu:18.04"},{"Deleted":"sha256:71eaf13299f4"}]"#;
+        let missing = r#"{"message":"No such image: nope:1"}"#;
+        let responses = vec![
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{deleted}",
+                deleted.len()
+            ),
+            format!(
+                "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{missing}\r\n0\r\n\r\n",
+                missing.len()
+            ),
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]".to_string(),
+        ];
+        let (client, server) = fake_daemon_with("batch", vec![responses]);
+
+        let results = client.remove_images(&["ubuntu:18.04", "nope:1", "work/app:1"]);
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "DELETE /images/ubuntu:18.04 HTTP/1.1",
+                "DELETE /images/nope:1 HTTP/1.1",
+                "DELETE /images/work/app:1 HTTP/1.1"
+            ]
+        );
+        assert!(results[0].as_ref().unwrap());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            "No such image: nope:1"
+        );
+        // Only untagged
+        assert!(!results[2].as_ref().unwrap());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::args::Kind;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can stop a command. Each kind of failure exits with its own code, see
+/// [`Error::exit_code`], so scripts can tell them apart.
+#[derive(Debug)]
+pub enum Error {
+    /// None of the runtimes we could use are installed
+    RuntimeNotFound(String),
+    /// The runtime is installed but its daemon isn't responding
+    DaemonUnreachable {
+        runtime: String,
+        message: String,
+    },
+    /// The runtime printed something we couldn't make sense of
+    UnparsableOutput {
+        command: String,
+        line: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The runtime refused to do something, like removing an image in use
+    Runtime(String),
+    InvalidQuery(String),
+    InvalidTimestamp {
+        value: String,
+        source: jiff::Error,
+    },
+    /// A policy or protection file couldn't be read or parsed
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+    /// `rm` wasn't confirmed and couldn't ask, says what would have been removed
+    ConfirmationRequired(Kind),
+    Io(io::Error),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Io(_) | Self::Runtime(_) => 1,
+            // 2 is what clap uses for invalid arguments
+            Self::RuntimeNotFound(_) => 3,
+            Self::DaemonUnreachable { .. } => 4,
+            Self::UnparsableOutput { .. } => 5,
+            Self::InvalidQuery(_) => 6,
+            Self::InvalidTimestamp { .. } => 7,
+            Self::Config { .. } => 8,
+            Self::ConfirmationRequired(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RuntimeNotFound(runtime) => {
+                write!(f, "couldn't find a container runtime ({runtime})")
+            }
+            Self::DaemonUnreachable { runtime, message } => {
+                write!(f, "couldn't reach the {runtime} daemon: {message}")
+            }
+            Self::UnparsableOutput {
+                command,
+                line,
+                source,
+            } => write!(
+                f,
+                "couldn't parse the output of `{command}`: {source}\n  offending output: {line}"
+            ),
+            Self::Runtime(message) => write!(f, "{message}"),
+            Self::InvalidQuery(message) => write!(f, "invalid query: {message}"),
+            Self::InvalidTimestamp { value, source } => {
+                write!(f, "invalid timestamp '{value}': {source}")
+            }
+            Self::Config { path, message } => {
+                write!(f, "couldn't load {}: {message}", path.display())
+            }
+            Self::ConfirmationRequired(kind) => write!(
+                f,
+                "refusing to remove {} without confirmation, pass --yes when stdin isn't a terminal",
+                kind.plural()
+            ),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::UnparsableOutput { source, .. } => Some(source.as_ref()),
+            Self::InvalidTimestamp { source, .. } => Some(source),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -1,6 +1,8 @@
-use crate::{docker, podman};
+use crate::reference::Reference;
+use crate::{docker, engine, podman};
 use jiff::Timestamp;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 
 #[derive(Deserialize)]
 #[serde(untagged)]
@@ -9,13 +11,173 @@ pub enum ImageOutput {
     Docker(docker::Image),
 }
 
-#[derive(Debug, Clone, Eq, Hash, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
 pub struct Image {
     pub hash: String,
+    /// The repository and tag of the first name, where the image has several
     pub repository: String,
     pub tag: String,
+    /// Every `repository:tag` the image goes by, empty if it's dangling
+    pub references: Vec<String>,
     pub size: usize,
     pub created_at: Timestamp,
+    /// The manifest digest, only known if the image was pulled from or pushed to a registry
+    pub digest: Option<String>,
+    pub repo_digests: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+    /// ID of the image this one was built on top of, only podman and the Engine API say
+    pub parent: Option<String>,
+    /// Bytes in layers other images use too, when the runtime has worked it out
+    pub shared_size: Option<usize>,
+}
+
+impl Image {
+    /// Bytes only this image uses, what removing it frees if nothing else is removed with it.
+    pub fn unique_size(&self) -> Option<usize> {
+        self.shared_size
+            .map(|shared| self.size.saturating_sub(shared))
+    }
+
+    /// The name split into registry, namespace, repository and tag. `None` for dangling images,
+    /// or if the runtime gave a name that isn't a valid reference.
+    pub fn reference(&self) -> Option<Reference> {
+        if self.is_dangling() {
+            return None;
+        }
+        if self.tag.is_empty() {
+            Reference::parse(&self.repository)
+        } else {
+            Reference::parse(&format!("{}:{}", self.repository, self.tag))
+        }
+    }
+
+    /// An image with no repository, what docker shows as `<none>:<none>`.
+    pub fn is_dangling(&self) -> bool {
+        self.repository.is_empty()
+    }
+
+    /// The fully qualified repository, so `ubuntu` and `docker.io/library/ubuntu` are the same
+    /// one. A name that isn't a valid reference is left as it is.
+    pub fn repository_name(&self) -> String {
+        match self.reference() {
+            Some(reference) => reference.name(),
+            None => self.repository.clone(),
+        }
+    }
+
+    /// Position of this image among the others in the same repository ordered newest first,
+    /// starting at 1. Dangling images aren't in any repository so don't have one.
+    pub fn rank_in_repository<'a>(
+        &self,
+        images: impl IntoIterator<Item = &'a Image>,
+    ) -> Option<usize> {
+        if self.is_dangling() {
+            return None;
+        }
+        // Ties on the creation time are broken by tag then ID so the order is stable
+        let key = |img: &Image| {
+            (
+                std::cmp::Reverse(img.created_at),
+                img.tag.clone(),
+                img.hash.clone(),
+            )
+        };
+        let own_key = key(self);
+        let repository = self.repository_name();
+        let newer = images
+            .into_iter()
+            .filter(|img| key(img) < own_key && img.repository_name() == repository)
+            .count();
+        Some(newer + 1)
+    }
+}
+
+/// One of an image's names, removing it untags the image and only the last one deletes it.
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct ImageReference {
+    pub name: String,
+    /// ID of the image it names
+    pub image: String,
+}
+
+impl ImageReference {
+    pub fn parse(&self) -> Option<Reference> {
+        Reference::parse(&self.name)
+    }
+
+    /// Every image's names, in the order of the images.
+    pub fn all<'a>(images: impl IntoIterator<Item = &'a Image>) -> Vec<Self> {
+        images
+            .into_iter()
+            .flat_map(|img| {
+                img.references.iter().map(|name| Self {
+                    name: name.clone(),
+                    image: img.hash.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// `docker image ls` prints a row per name, this gathers them back into one image each with
+/// every name, keeping the order the images were first seen in.
+pub fn merge_names(images: Vec<Image>) -> Vec<Image> {
+    let mut merged: Vec<Image> = Vec::with_capacity(images.len());
+    let mut index = BTreeMap::new();
+    for image in images {
+        match index.get(&image.hash) {
+            Some(&i) => {
+                let existing: &mut Image = &mut merged[i];
+                for name in image.references {
+                    if !existing.references.contains(&name) {
+                        existing.references.push(name);
+                    }
+                }
+                if existing.is_dangling() {
+                    existing.repository = image.repository;
+                    existing.tag = image.tag;
+                }
+            }
+            None => {
+                index.insert(image.hash.clone(), merged.len());
+                merged.push(image);
+            }
+        }
+    }
+    merged
+}
+
+/// `docker image ls` leaves out labels, this fills them in from inspecting the images.
+pub fn add_labels(images: &mut [Image], inspected: Vec<docker::ImageInspect>) {
+    let mut labels = inspected
+        .into_iter()
+        .map(|i| {
+            let id = i.id.trim_start_matches("sha256:").to_string();
+            (id, i.config.and_then(|c| c.labels).unwrap_or_default())
+        })
+        .collect::<BTreeMap<_, _>>();
+    for image in images {
+        if let Some(labels) = labels.remove(&image.hash) {
+            image.labels = labels;
+        }
+    }
+}
+
+/// Runtimes print `<none>` where there's no value.
+fn none_if_missing(s: Option<String>) -> Option<String> {
+    s.filter(|s| !s.is_empty() && s != "<none>")
+}
+
+/// Parent IDs are empty for base images, and have a `sha256:` prefix from the Engine API.
+fn parent_of(parent_id: &str) -> Option<String> {
+    Some(parent_id.trim_start_matches("sha256:").to_string()).filter(|p| !p.is_empty())
+}
+
+/// Pulls the digest out of a `repo@sha256:...` reference.
+fn digest_of(repo_digest: &str) -> Option<String> {
+    repo_digest
+        .split_once('@')
+        .map(|(_, digest)| digest.to_string())
 }
 
 impl From<ImageOutput> for Image {
@@ -29,35 +191,212 @@ impl From<ImageOutput> for Image {
 
 impl From<podman::Image> for Image {
     fn from(img: podman::Image) -> Self {
-        let (repository, tag) = if let Some(s) = img.names.get(0) {
-            let mut parts = s.split(":");
-            let repo = parts.next().unwrap().to_string();
-            let tag = parts.next().unwrap_or_else(|| "latest").to_string();
-            (repo, tag)
-        } else {
-            (String::new(), String::new())
-        };
+        let (repository, tag) = img
+            .names
+            .first()
+            .map(|name| repository_and_tag(name))
+            .unwrap_or_default();
+        let digest = none_if_missing(Some(img.digest))
+            .or_else(|| img.repo_digests.first().and_then(|d| digest_of(d)));
         Self {
             hash: img.id,
             repository,
             tag,
+            references: img.names,
             size: img.size,
             created_at: img.created_at,
+            digest,
+            repo_digests: img.repo_digests,
+            labels: img.labels.unwrap_or_default(),
+            parent: parent_of(&img.parent_id),
+            shared_size: img.shared_size,
         }
     }
 }
 
 impl From<docker::Image> for Image {
     fn from(img: docker::Image) -> Self {
-        let repository = img.repository.unwrap_or_default();
-        let tag = img.tag.unwrap_or_default();
-        let hash = img.id;
+        let repository = none_if_missing(img.repository).unwrap_or_default();
+        let tag = none_if_missing(img.tag).unwrap_or_default();
+        // With `--no-trunc` the ID has the digest's `sha256:` prefix
+        let hash = img.id.trim_start_matches("sha256:").to_string();
+        let references = if repository.is_empty() || tag.is_empty() {
+            vec![]
+        } else {
+            vec![format!("{repository}:{tag}")]
+        };
         Self {
             repository,
             tag,
+            references,
             hash,
             created_at: img.created_at,
             size: img.size.to_bytes() as usize,
+            digest: none_if_missing(Some(img.digest)),
+            shared_size: img.shared_size.map(|s| s as usize),
+            ..Default::default()
+        }
+    }
+}
+
+/// Splits a name into the repository as the runtime wrote it and the tag, which is `latest`
+/// if left out. A registry port isn't mistaken for the tag. Names that aren't valid references
+/// are kept whole as the repository.
+fn repository_and_tag(name: &str) -> (String, String) {
+    let Some(reference) = Reference::parse(name) else {
+        return (name.to_string(), String::new());
+    };
+    let repository = name.split_once('@').map_or(name, |(name, _)| name);
+    let repository = match &reference.tag {
+        Some(tag) => &repository[..repository.len() - tag.len() - 1],
+        None => repository,
+    };
+    let tag = reference.tag_or_latest().unwrap_or_default();
+    (repository.to_string(), tag.to_string())
+}
+
+impl From<engine::Image> for Image {
+    fn from(img: engine::Image) -> Self {
+        let references = img
+            .repo_tags
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|t| t.as_str() != "<none>:<none>")
+            .collect::<Vec<_>>();
+        let (repository, tag) = references
+            .first()
+            .map(|t| repository_and_tag(t))
+            .unwrap_or_default();
+        let repo_digests = img.repo_digests.unwrap_or_default();
+        Self {
+            hash: img.id.trim_start_matches("sha256:").to_string(),
+            repository,
+            tag,
+            references,
+            size: img.size.max(0) as usize,
+            created_at: Timestamp::from_second(img.created).unwrap_or_default(),
+            digest: repo_digests.first().and_then(|d| digest_of(d)),
+            repo_digests,
+            labels: img.labels.unwrap_or_default(),
+            parent: parent_of(&img.parent_id),
+            // -1 when the daemon didn't calculate it
+            shared_size: usize::try_from(img.shared_size).ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn podman_image(name: &str) -> podman::Image {
+        podman::Image {
+            id: "e9d2252ab371a1149d3ef64b7793a274375dee5d9ec61b9e4fb41d75f156c1a1".to_string(),
+            parent_id: String::new(),
+            size: 82_756_709,
+            history: vec![],
+            names: vec![name.to_string()],
+            digest: String::new(),
+            repo_digests: vec![],
+            labels: None,
+            shared_size: None,
+            created: 1_750_414_636,
+            created_at: Timestamp::from_second(1_750_414_636).unwrap(),
         }
     }
+
+    fn docker_image(id: &str) -> docker::Image {
+        docker_image_sharing(id, "N/A")
+    }
+
+    fn docker_image_sharing(id: &str, shared_size: &str) -> docker::Image {
+        let json = format!(
+            r#"{{"CreatedAt":"2022-10-25 02:53:28 +0100 BST","Digest":"\u003cnone\u003e","ID":"{id}","Repository":"ubuntu","SharedSize":"{shared_size}","Size":"63.1MB","Tag":"18.04"}}"#
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn digests_and_labels_are_kept() {
+        let digest = "sha256:cdf755952ed117f6126ff4e65810bf93767d4c38f5c7185b50ec1f1078b464cc";
+        let mut podman = podman_image("docker.io/library/ubuntu:24.10");
+        podman.repo_digests = vec![format!("docker.io/library/ubuntu@{digest}")];
+        podman.labels = Some(BTreeMap::from([(
+            "org.opencontainers.image.version".to_string(),
+            "24.10".to_string(),
+        )]));
+        let image = Image::from(podman.clone());
+        // Taken from the repo digests when podman leaves `Digest` out
+        assert_eq!(image.digest.as_deref(), Some(digest));
+        assert_eq!(image.labels["org.opencontainers.image.version"], "24.10");
+
+        podman.digest =
+            "sha256:f995e05e8adc3292853cc37e6edda72351f8002ce7469a29322d19e01529cb9f".to_string();
+        let image = Image::from(podman.clone());
+        assert_eq!(image.digest, Some(podman.digest));
+
+        assert_eq!(Image::from(docker_image("71eaf13299f4")).digest, None);
+    }
+
+    #[test]
+    fn docker_ids_are_full_length() {
+        // So an `--id-prefix` longer than the 12 characters docker shows by default still matches
+        let full = "71eaf13299f4c0bd2e4b7b8d9e1a5c3f6d0e2b4a8c7f9e1d3b5a7c9e0f2d4b6a";
+        let image = Image::from(docker_image(&format!("sha256:{full}")));
+        assert_eq!(image.hash, full);
+    }
+
+    #[test]
+    fn unique_size_needs_the_shared_size() {
+        // `N/A` until docker has worked it out
+        let image = Image::from(docker_image("71eaf13299f4"));
+        assert_eq!((image.shared_size, image.unique_size()), (None, None));
+        let image = Image::from(docker_image_sharing("71eaf13299f4", "20MB"));
+        assert_eq!(image.shared_size, Some(20_000_000));
+        assert_eq!(image.unique_size(), Some(43_100_000));
+    }
+
+    #[test]
+    fn labels_come_from_inspecting() {
+        let full = "71eaf13299f4c0bd2e4b7b8d9e1a5c3f6d0e2b4a8c7f9e1d3b5a7c9e0f2d4b6a";
+        let mut images = vec![
+            Image::from(docker_image(&format!("sha256:{full}"))),
+            Image::from(docker_image("sha256:0000")),
+        ];
+        let inspect = format!(
+            r#"[{{"Id":"sha256:{full}","RootFS":{{"Type":"layers","Layers":[]}},"Config":{{"Labels":{{"docker-cleanup.protect":"true"}}}}}}]"#
+        );
+        add_labels(&mut images, serde_json::from_str(&inspect).unwrap());
+        assert_eq!(images[0].labels["docker-cleanup.protect"], "true");
+        assert!(images[1].labels.is_empty());
+    }
+
+    #[test]
+    fn names_are_split_into_repository_and_tag() {
+        let split = |name| {
+            let image = Image::from(podman_image(name));
+            (image.repository, image.tag)
+        };
+        let owned = |repository: &str, tag: &str| (repository.to_string(), tag.to_string());
+        assert_eq!(
+            split("docker.io/library/ubuntu:24.10"),
+            owned("docker.io/library/ubuntu", "24.10")
+        );
+        // The registry's port isn't the tag
+        assert_eq!(
+            split("localhost:5000/app:1.2"),
+            owned("localhost:5000/app", "1.2")
+        );
+        // Neither is a digest, and a name without a tag is the latest
+        assert_eq!(
+            split(
+                "localhost:5000/app:1.2@sha256:cdf755952ed117f6126ff4e65810bf93767d4c38f5c7185b50ec1f1078b464cc"
+            ),
+            owned("localhost:5000/app", "1.2")
+        );
+        assert_eq!(
+            split("localhost:5000/app"),
+            owned("localhost:5000/app", "latest")
+        );
+    }
 }
@@ -0,0 +1,155 @@
+use crate::error::{Error, Result};
+use crate::{docker, podman};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Digest of the empty tarball, what steps like `WORKDIR` or `ENV` leave as their layer.
+pub const EMPTY_LAYER: &str =
+    "sha256:5f70bf18a086007016e948b04aed3b82103a36bea41755b6cddfaf10ace3c6ef";
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum HistoryOutput {
+    Podman(podman::History),
+    Docker(docker::History),
+}
+
+impl HistoryOutput {
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Podman(p) => p.size.max(0) as u64,
+            Self::Docker(d) => d.size,
+        }
+    }
+}
+
+/// One filesystem layer, images built from the same base share them.
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct Layer {
+    /// The uncompressed digest from the image's RootFS
+    pub digest: String,
+    pub size: u64,
+}
+
+impl Layer {
+    /// Neither runtime reports layer sizes next to the digests, so they're paired with the sizes
+    /// in the image history of image `id`. `digests` are oldest first like RootFS, `sizes`
+    /// newest first like history.
+    ///
+    /// Nothing links a history step to its layer, so the pairing is a guess: steps which didn't
+    /// add anything show up with no size in the history, but may or may not have an (empty)
+    /// layer, so both are skipped and the rest are paired in order. If that leaves a different
+    /// number of each the guess is wrong, and it's an error rather than sizes on the wrong layers.
+    pub fn from_history(
+        id: &str,
+        digests: Vec<String>,
+        sizes: impl DoubleEndedIterator<Item = u64>,
+    ) -> Result<Vec<Self>> {
+        let sizes = sizes.rev().filter(|size| *size > 0).collect::<Vec<_>>();
+        let non_empty = digests.iter().filter(|d| *d != EMPTY_LAYER).count();
+        if sizes.len() != non_empty {
+            return Err(Error::Runtime(format!(
+                "image {id} has {non_empty} layers but {} history steps with a size, so their sizes are unknown",
+                sizes.len()
+            )));
+        }
+        let mut sizes = sizes.into_iter();
+        Ok(digests
+            .into_iter()
+            .map(|digest| {
+                let size = if digest == EMPTY_LAYER {
+                    0
+                } else {
+                    sizes.next().unwrap_or_default()
+                };
+                Self { digest, size }
+            })
+            .collect())
+    }
+}
+
+/// The layers of some images that might be removed, and the layers every other image uses. It's
+/// looked up before removing anything so that what was freed can be worked out afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayerUsage {
+    /// Digests of the layers images other than these use
+    pub kept: HashSet<String>,
+    /// Each image's layers as `(digest, size)`, keyed by image ID
+    pub layers: HashMap<String, Vec<(String, u64)>>,
+    /// What each image without layers is guessed to free, keyed by image ID
+    pub unlayered: HashMap<String, u64>,
+}
+
+impl LayerUsage {
+    /// Bytes freed by removing the images with these IDs, the layers they use which no other
+    /// image does. The rest of the images this knows about count as kept.
+    pub fn reclaimable(&self, ids: &[&str]) -> u64 {
+        let removing = ids.iter().copied().collect::<HashSet<_>>();
+        let mut kept = self.kept.iter().map(String::as_str).collect::<HashSet<_>>();
+        let mut removed = HashMap::new();
+        for (id, layers) in &self.layers {
+            let layers = layers.iter().map(|(digest, size)| (digest.as_str(), *size));
+            if removing.contains(id.as_str()) {
+                removed.extend(layers);
+            } else {
+                kept.extend(layers.map(|(digest, _)| digest));
+            }
+        }
+        let layered = removed
+            .into_iter()
+            .filter(|(digest, _)| !kept.contains(digest))
+            .map(|(_, size)| size)
+            .sum::<u64>();
+        let unlayered = self
+            .unlayered
+            .iter()
+            .filter(|(id, _)| removing.contains(id.as_str()))
+            .map(|(_, size)| size)
+            .sum::<u64>();
+        layered + unlayered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_sizes_pair_with_layers() {
+        // A zero byte layer is left out of the history sizes
+        let layers = Layer::from_history(
+            "a1",
+            vec![
+                "sha256:base".to_string(),
+                EMPTY_LAYER.to_string(),
+                "sha256:app".to_string(),
+            ],
+            [20, 0, 0, 100].into_iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            layers.iter().map(|l| l.size).collect::<Vec<_>>(),
+            [100, 0, 20]
+        );
+        // Rather than guessing which size goes with which layer when the counts differ
+        let mismatched = Layer::from_history(
+            "a1",
+            vec!["sha256:base".to_string(), "sha256:app".to_string()],
+            [20, 5, 100].into_iter(),
+        );
+        assert!(mismatched.is_err());
+    }
+
+    #[test]
+    fn can_deserialize_history() {
+        let docker: HistoryOutput = serde_json::from_str(
+            r#"{"Comment":"","CreatedAt":"2025-06-20T10:17:16Z","CreatedBy":"/bin/sh -c #(nop) ADD file:...","CreatedSince":"4 weeks ago","ID":"<missing>","Size":"78123456"}"#,
+        )
+        .unwrap();
+        assert_eq!(docker.size(), 78_123_456);
+        let podman: HistoryOutput =
+            serde_json::from_str(r#"{"id":"e9d2252ab371","created":"2025-06-20T10:17:16Z","CreatedBy":"/bin/sh","size":5000,"comment":""}"#)
+                .unwrap();
+        assert_eq!(podman.size(), 5000);
+    }
+}
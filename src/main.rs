@@ -1,43 +1,509 @@
 use adapter::*;
-use args::Commands;
+use args::{ApplyOptions, Commands, FilterOptions, Kind, OutputFormat, QueryOptions};
+use backend::{Backend, ImageRemoval};
 use clap::Parser;
-use human_size::{SpecificSize, multiples::*};
-use std::collections::BTreeMap;
-use std::process::Command;
-use std::sync::Arc;
+use error::{Error, Result};
+use jiff::Zoned;
+use output::{Removable, Removal, Summary};
+use policy::{Action, Policy};
+use protect::Protection;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::{IsTerminal, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
 use trustfall::{FieldValue, execute_query};
 
 mod adapter;
 mod args;
+pub mod backend;
+pub mod build_cache;
+mod confirm;
+pub mod containers;
 pub mod docker;
+pub mod engine;
+mod error;
 pub mod images;
+pub mod layers;
+pub mod networks;
+mod output;
 pub mod podman;
+mod policy;
+mod protect;
+pub mod reference;
+pub mod volumes;
 
+pub use build_cache::BuildCache;
+pub use containers::*;
 pub use images::*;
+pub use layers::*;
+pub use networks::*;
+pub use volumes::*;
 
 fn main() {
     let args = args::Cli::parse();
+    if let Err(e) = run(args) {
+        eprintln!("error: {e}");
+        std::process::exit(e.exit_code());
+    }
+}
 
-    let filter = args.command.filter();
+fn run(args: args::Cli) -> Result<()> {
+    let backend = backend::select(args.runtime)?;
 
-    let mut query_str = "{Image{".to_string();
+    let Some(filter) = args.command.filter() else {
+        return match &args.command {
+            Commands::Query(opts) => run_query(backend, opts),
+            Commands::Apply(opts) => apply_policy(backend, opts),
+            _ => Ok(()),
+        };
+    };
 
-    if let Some(created_before) = filter.created_before {
-        query_str.push_str(&format!(
+    if let Commands::Print(_) = args.command {
+        if let Ok(info) = backend.info() {
+            println!("# runtime: {} {}", info.name, info.version);
+        }
+        for kind in filter.kinds() {
+            let (query_str, _) = match kind {
+                Kind::Container => output::ContainerRecord::query(filter)?,
+                Kind::Image => build_query(filter)?,
+                Kind::Volume => output::VolumeRecord::query(filter)?,
+                Kind::BuildCache => output::BuildCacheRecord::query(filter)?,
+                Kind::Network => output::NetworkRecord::query(filter)?,
+            };
+            println!("{query_str}");
+        }
+        return Ok(());
+    }
+
+    if let Commands::Untag(_) = args.command {
+        return run_untag(backend, filter);
+    }
+
+    // Each kind is queried after the ones before it were removed, so images only the removed
+    // containers used count as unused
+    for kind in filter.kinds() {
+        let backend = backend.clone();
+        let command = &args.command;
+        match kind {
+            Kind::Container => run_kind::<output::ContainerRecord>(backend, kind, command, filter)?,
+            Kind::Image => run_images(backend, command, filter)?,
+            Kind::Volume => run_kind::<output::VolumeRecord>(backend, kind, command, filter)?,
+            Kind::BuildCache => {
+                run_kind::<output::BuildCacheRecord>(backend, kind, command, filter)?
+            }
+            Kind::Network => run_kind::<output::NetworkRecord>(backend, kind, command, filter)?,
+        }
+    }
+    Ok(())
+}
+
+/// The records of a kind other than images, each kind's module says how to query, summarise
+/// and remove them. Images need the runtime to work out what removing them frees and are
+/// removed in order of their parents, so they have their own.
+trait Record: Removable + Sized {
+    type Summary: Summary;
+
+    /// Turns the filter options into a trustfall query over this kind and the variables it
+    /// uses.
+    fn query(filter: &FilterOptions) -> Result<(String, QueryArgs)>;
+    /// The record for a row the query returned
+    fn from_row(row: output::Row) -> Self;
+    /// Orders them for `--sort`, largest first with unknown sizes last
+    fn sort(records: &mut [Self]) {
+        records.sort_by_key(|r| std::cmp::Reverse(r.size()));
+    }
+    fn summarise(records: &[Self]) -> Self::Summary;
+    /// Removes a batch, with a result for each reference
+    fn remove(backend: &dyn Backend, references: &[&str]) -> Vec<Result<()>>;
+}
+
+/// Variables for a trustfall query.
+type QueryArgs = BTreeMap<Arc<str>, FieldValue>;
+
+/// Runs the query for the filters returning the matching records, sorted if asked to.
+fn select<T: Record>(backend: Arc<dyn Backend>, filter: &FilterOptions) -> Result<Vec<T>> {
+    let (query_str, query_args) = T::query(filter)?;
+    let mut records = execute(backend, &query_str, query_args)?
+        .into_iter()
+        .map(T::from_row)
+        .collect::<Vec<_>>();
+    if filter.sort {
+        T::sort(&mut records);
+    }
+    Ok(records)
+}
+
+fn run_kind<T: Record>(
+    backend: Arc<dyn Backend>,
+    kind: Kind,
+    command: &Commands,
+    filter: &FilterOptions,
+) -> Result<()> {
+    let records = select::<T>(backend.clone(), filter)?;
+    match command {
+        Commands::Ls(_) => list(&records, filter.format),
+        Commands::Size(_) => print_summary(&T::summarise(&records), filter.format),
+        Commands::Rm(_) => rm(
+            kind,
+            records,
+            filter,
+            |records| Ok(T::summarise(records)),
+            |records, protection, report| {
+                let remove = |references: &[&str]| T::remove(backend.as_ref(), references);
+                remove_batches(records, protection, filter.jobs, remove, report)
+            },
+        )?,
+        _ => unreachable!("handled in run"),
+    }
+    Ok(())
+}
+
+fn run_images(backend: Arc<dyn Backend>, command: &Commands, filter: &FilterOptions) -> Result<()> {
+    let images = select_images(backend.clone(), filter)?;
+    match command {
+        Commands::Ls(_) => list(&images, filter.format),
+        Commands::Size(_) => print_summary(&size_summary(backend, &images)?, filter.format),
+        Commands::Rm(_) => rm(
+            Kind::Image,
+            images,
+            filter,
+            |images| {
+                let freeing = images
+                    .iter()
+                    .filter(|i| i.frees_space())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                size_summary(backend.clone(), &freeing)
+            },
+            |images, protection, report| {
+                remove_images(backend.as_ref(), images, protection, filter.jobs, report)
+            },
+        )?,
+        _ => unreachable!("handled in run"),
+    }
+    Ok(())
+}
+
+/// Like `rm --untag` but with a result for every name, saying which untags deleted an image.
+fn run_untag(backend: Arc<dyn Backend>, filter: &FilterOptions) -> Result<()> {
+    if filter.kinds() != [Kind::Image] {
+        return Err(Error::InvalidQuery(
+            "untag only works on images".to_string(),
+        ));
+    }
+    if filter.include_untagged || filter.only_untagged {
+        return Err(Error::InvalidQuery(
+            "untagged images have no names to untag".to_string(),
+        ));
+    }
+    let (query_str, query_args) = build_query(filter)?;
+    let mut images = find_images(backend.clone(), &query_str, query_args, false, true)?;
+    if filter.sort {
+        images.sort_by_key(|i| std::cmp::Reverse(i.size));
+    }
+    let names = plan_untags(&images.iter().flat_map(image_names).collect::<Vec<_>>());
+    rm(
+        Kind::Image,
+        names,
+        filter,
+        |names| {
+            let deleted = names
+                .iter()
+                .filter(|n| n.deletes_image)
+                .map(|n| n.image.as_str())
+                .collect::<HashSet<_>>();
+            let deleted = images
+                .iter()
+                .filter(|i| deleted.contains(i.id.as_str()))
+                .cloned()
+                .collect::<Vec<_>>();
+            let usage = layer_usage(backend.clone(), &deleted)?;
+            let ids = deleted.iter().map(|i| i.id.as_str()).collect::<Vec<_>>();
+            Ok(output::UntagSummary {
+                names: names.len(),
+                images_deleted: deleted.len(),
+                reclaimable: usage.reclaimable(&ids),
+                usage,
+            })
+        },
+        |names, protection, report| {
+            untag_names(
+                backend.as_ref(),
+                &images,
+                names,
+                protection,
+                filter.jobs,
+                report,
+            )
+        },
+    )
+}
+
+/// The names `rm --untag` would remove from the image, one record each.
+fn image_names(image: &output::ImageRecord) -> Vec<output::ReferenceRecord> {
+    image
+        .untag
+        .iter()
+        .flatten()
+        .map(|name| {
+            let reference = reference::Reference::parse(name);
+            output::ReferenceRecord {
+                name: name.clone(),
+                image: image.id.clone(),
+                registry: reference.as_ref().map(|r| r.registry.clone()),
+                namespace: reference.as_ref().map(|r| r.namespace.clone()),
+                repo: reference.as_ref().map(|r| r.repository.clone()),
+                tag: reference
+                    .as_ref()
+                    .and_then(|r| r.tag_or_latest())
+                    .map(|t| t.to_string()),
+                size: image.size,
+                labels: image.labels.clone(),
+                deletes_image: false,
+                image_names: image.references.len(),
+            }
+        })
+        .collect()
+}
+
+/// Marks the last of an image's names as deleting it when every one of its names is being
+/// untagged. It's worked out again after confirming, as fewer names may have been picked.
+fn plan_untags(names: &[output::ReferenceRecord]) -> Vec<output::ReferenceRecord> {
+    let mut untagged = HashMap::<&str, usize>::new();
+    for name in names {
+        *untagged.entry(name.image.as_str()).or_default() += 1;
+    }
+    let mut seen = HashMap::<&str, usize>::new();
+    names
+        .iter()
+        .map(|name| {
+            let seen = seen.entry(name.image.as_str()).or_default();
+            *seen += 1;
+            let count = untagged[name.image.as_str()];
+            output::ReferenceRecord {
+                deletes_image: count == name.image_names && *seen == count,
+                ..name.clone()
+            }
+        })
+        .collect()
+}
+
+/// Untags the names, those that only untag first and then those expected to delete their
+/// image, children before their parents. Whether a name did delete its image is what the
+/// runtime says, which it won't if the image kept another name or has images built on it.
+fn untag_names(
+    backend: &dyn Backend,
+    images: &[output::ImageRecord],
+    names: &[output::ReferenceRecord],
+    protection: &Protection,
+    jobs: usize,
+    mut report: impl FnMut(&Removal<output::ReferenceRecord>),
+) -> Vec<Removal<output::ReferenceRecord>> {
+    let deleted = Mutex::new(HashSet::new());
+    let remove = |names: &[&str]| {
+        let results = backend.remove_images(names);
+        let mut deleted = deleted.lock().unwrap();
+        names
+            .iter()
+            .zip(results)
+            .map(|(name, result)| {
+                if let Ok(ImageRemoval::Deleted) = result {
+                    deleted.insert(name.to_string());
+                }
+                result.map(|_| ())
+            })
+            .collect()
+    };
+    let outcome = |removal: &Removal<output::ReferenceRecord>| {
+        let mut removal = removal.clone();
+        removal.item.deletes_image = deleted.lock().unwrap().contains(&removal.item.name);
+        removal
+    };
+    let mut report = |removal: &Removal<output::ReferenceRecord>| report(&outcome(removal));
+
+    let (last, others): (Vec<_>, Vec<_>) = plan_untags(names)
+        .into_iter()
+        .partition(|n| n.deletes_image);
+    let mut removals = remove_batches(&others, protection, jobs, remove, &mut report);
+    for level in removal_levels(images) {
+        let level = level.iter().map(|i| i.id.as_str()).collect::<HashSet<_>>();
+        let level = last
+            .iter()
+            .filter(|n| level.contains(n.image.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        removals.extend(remove_batches(
+            &level,
+            protection,
+            jobs,
+            remove,
+            &mut report,
+        ));
+    }
+    removals.iter().map(outcome).collect()
+}
+
+fn print_summary<S: fmt::Display + Serialize>(summary: &S, format: OutputFormat) {
+    if format == OutputFormat::Table {
+        println!("{summary}");
+    } else {
+        output::print_record(summary, format);
+    }
+}
+
+/// Removes what the filters selected after asking, unless `--yes` was given. With `--dry-run`
+/// it's listed instead, along with what removing it would free. What it frees is worked out
+/// before removing anything, as afterwards there's no telling what the removed items shared.
+/// Protected items are left out of that and of the question, and reported as skipped.
+fn rm<T: Removable, S: Summary>(
+    kind: Kind,
+    items: Vec<T>,
+    filter: &FilterOptions,
+    summarise: impl FnOnce(&[T]) -> Result<S>,
+    remove: impl FnOnce(&[T], &Protection, &mut dyn FnMut(&Removal<T>)) -> Vec<Removal<T>>,
+) -> Result<()> {
+    let protection = Protection::from_options(&filter.protect)?;
+    let (protected, removable): (Vec<_>, Vec<_>) =
+        items.into_iter().partition(|i| protection.is_protected(i));
+    let planned = summarise(&removable)?;
+    if filter.dry_run {
+        list(&removable, filter.format);
+        if filter.format == OutputFormat::Table {
+            for item in &protected {
+                println!("{}: skipped (protected)", item.name());
+            }
+            println!("{planned}");
+        } else {
+            eprintln!("{planned}");
+        }
+        return Ok(());
+    }
+
+    let removable = if filter.yes {
+        removable
+    } else if std::io::stdin().is_terminal() {
+        confirm::confirm(
+            removable,
+            planned.reclaimable(),
+            &mut std::io::stdin().lock(),
+            &mut std::io::stderr(),
+        )?
+    } else {
+        return Err(Error::ConfirmationRequired(kind));
+    };
+    // The protected items go along so they're reported as skipped and counted in the summary
+    let items = protected.into_iter().chain(removable).collect::<Vec<_>>();
+    let removals = remove(&items, &protection, &mut |removal| {
+        output::print_removal(removal, filter.format)
+    });
+    let summary = output::RemovalSummary::with_summary(&removals, &planned);
+    if filter.format == OutputFormat::Table {
+        println!("{summary}");
+    } else {
+        // Keep stdout to just the records so it stays parseable
+        eprintln!("{summary}");
+    }
+    if matches!(filter.format, OutputFormat::Json | OutputFormat::Csv) {
+        output::print_records(&removals, filter.format);
+    }
+    Ok(())
+}
+
+/// `created_before`/`created_after` edges for the age filters, which images and volumes share.
+fn created_edges(filter: &FilterOptions) -> String {
+    let mut edges = String::new();
+    let now = Zoned::now();
+    if let Some(created_before) = filter.resolve_created_before(&now) {
+        edges.push_str(&format!(
             "created_before(timestamp: \"{}\")\n",
             created_before
         ));
     }
 
-    if let Some(created_before) = filter.created_after {
-        query_str.push_str(&format!(
+    if let Some(created_before) = filter.resolve_created_after(&now) {
+        edges.push_str(&format!(
             "created_after(timestamp: \"{}\")\n",
             created_before
         ));
     }
+    edges
+}
 
-    let mut query_args: BTreeMap<Arc<str>, trustfall::FieldValue> = BTreeMap::new();
+/// Filters that apply to none of the kinds asked for are refused rather than ignored, so they
+/// can't silently select more than intended. With several kinds, each query leaves out the
+/// filters that don't apply to its kind.
+fn check_filters(filter: &FilterOptions) -> Result<()> {
+    use Kind::*;
+    let sized: &[Kind] = &[Container, Image, Volume, BuildCache];
+    let labelled: &[Kind] = &[Image, Volume, Network];
+    let filters: &[(&str, bool, &[Kind])] = &[
+        (
+            "--id-prefix",
+            filter.id_prefix.is_some(),
+            &[Container, Image, BuildCache, Network],
+        ),
+        ("--include-untagged", filter.include_untagged, &[Image]),
+        ("--only-untagged", filter.only_untagged, &[Image]),
+        ("--keep-latest", filter.keep_latest.is_some(), &[Image]),
+        ("--larger-than", filter.larger_than.is_some(), sized),
+        ("--smaller-than", filter.smaller_than.is_some(), sized),
+        ("--used-before", filter.used_before.is_some(), &[BuildCache]),
+        ("--unused-for", filter.unused_for.is_some(), &[BuildCache]),
+        (
+            "--exited-before",
+            filter.exited_before.is_some(),
+            &[Container],
+        ),
+        ("--exit-code", !filter.exit_code.is_empty(), &[Container]),
+        ("--image", filter.image.is_some(), &[Container]),
+        ("--registry", !filter.registry.is_empty(), &[Image]),
+        ("--untag", filter.untag, &[Image]),
+        ("--label", !filter.labels.is_empty(), labelled),
+        (
+            "--exclude-label",
+            !filter.exclude_labels.is_empty(),
+            labelled,
+        ),
+        (
+            "--unused",
+            filter.unused,
+            &[Image, Volume, BuildCache, Network],
+        ),
+    ];
+    let kinds = filter.kinds();
+    let refused = filters
+        .iter()
+        .find(|(_, given, applies_to)| *given && !kinds.iter().any(|k| applies_to.contains(k)));
+    match refused {
+        Some((flag, ..)) => {
+            let kinds = kinds.iter().map(|k| k.plural()).collect::<Vec<_>>();
+            Err(Error::InvalidQuery(format!(
+                "{flag} doesn't apply to {}",
+                kinds.join(" or ")
+            )))
+        }
+        None => Ok(()),
+    }
+}
 
+/// Filters for the `id` property.
+fn id_filters(filter: &FilterOptions, query_args: &mut QueryArgs) -> String {
+    let mut id_filter = String::new();
+    if let Some(prefix) = &filter.id_prefix {
+        id_filter.push_str(r#"@filter(op: "has_prefix", value: ["$id_prefix"])"#);
+        id_filter.push('\n');
+        let prefix = prefix.trim_start_matches("sha256:");
+        query_args.insert(Arc::from("id_prefix".to_string()), prefix.into());
+    }
+    id_filter
+}
+
+/// Filters for the `size` property.
+fn size_filters(filter: &FilterOptions, query_args: &mut QueryArgs) -> String {
     let mut size_filter = String::new();
     if let Some(smaller_than) = filter.smaller_than {
         size_filter.push_str(r#"@filter(op: "<", value: ["$smaller_than"])"#);
@@ -55,7 +521,11 @@ fn main() {
             FieldValue::Int64(larger_than as i64),
         );
     }
+    size_filter
+}
 
+/// Filters for the `name` property.
+fn name_filters(filter: &FilterOptions, query_args: &mut QueryArgs) -> String {
     let mut name_filter = String::new();
 
     if let Some(contains) = &filter.name_contains {
@@ -75,91 +545,615 @@ fn main() {
         name_filter.push('\n');
         query_args.insert(Arc::from("name_eq".to_string()), contains.into());
     }
+    name_filter
+}
+
+/// `--label` and `--exclude-label` as filters on the `labels` property.
+fn label_filters(filter: &FilterOptions, query_args: &mut QueryArgs) -> String {
+    let mut label_filter = String::new();
+    if !filter.exclude_labels.is_empty() {
+        label_filter.push_str("labels");
+        for (i, label) in filter.exclude_labels.iter().enumerate() {
+            let var = format!("exclude_label_{i}");
+            label_filter.push_str(&format!(
+                r#" @filter(op: "not_contains", value: ["${var}"])"#
+            ));
+            query_args.insert(Arc::from(var), label.into());
+        }
+        label_filter.push('\n');
+    }
+
+    if !filter.labels.is_empty() {
+        label_filter.push_str("labels");
+        for (i, label) in filter.labels.iter().enumerate() {
+            let var = format!("label_{i}");
+            label_filter.push_str(&format!(r#" @filter(op: "contains", value: ["${var}"])"#));
+            query_args.insert(Arc::from(var), label.into());
+        }
+        label_filter.push('\n');
+    }
+    label_filter
+}
+
+/// Turns the filter options into a trustfall query over images and the variables it uses.
+fn build_query(filter: &FilterOptions) -> Result<(String, QueryArgs)> {
+    check_filters(filter)?;
+
+    let mut query_str = "{Image{".to_string();
+    query_str.push_str(&created_edges(filter));
+
+    let mut query_args: QueryArgs = BTreeMap::new();
+    let size_filter = size_filters(filter, &mut query_args);
+    let name_filter = name_filters(filter, &mut query_args);
+
+    if filter.unused {
+        query_str.push_str(
+            r#"used_by_containers @fold @transform(op: "count") @filter(op: "=", value: ["$no_containers"])"#,
+        );
+        query_str.push('\n');
+        query_args.insert(Arc::from("no_containers".to_string()), FieldValue::Int64(0));
+    }
+
+    let id_filter = id_filters(filter, &mut query_args);
+
+    let mut registry_filter = String::new();
+    if !filter.registry.is_empty() {
+        registry_filter.push_str(r#"@filter(op: "one_of", value: ["$registries"])"#);
+        registry_filter.push('\n');
+        let registries = filter
+            .registry
+            .iter()
+            .map(|r| reference::normalise_registry(r))
+            .collect::<Vec<_>>();
+        query_args.insert(Arc::from("registries".to_string()), registries.into());
+    }
+
+    query_str.push_str(&label_filters(filter, &mut query_args));
+
+    if let Some(keep) = filter.keep_latest {
+        query_str.push_str(r#"rank_in_repo @filter(op: ">", value: ["$keep_latest"])"#);
+        query_str.push('\n');
+        query_args.insert(
+            Arc::from("keep_latest".to_string()),
+            FieldValue::Int64(keep as i64),
+        );
+    }
+
+    if filter.only_untagged {
+        query_str.push_str(r#"dangling @filter(op: "=", value: ["$dangling"])"#);
+        query_str.push('\n');
+        query_args.insert(Arc::from("dangling".to_string()), true.into());
+    }
+
+    // An image matches the name filters if any of its names do
+    if !name_filter.is_empty() {
+        query_str.push_str(&format!(
+            "references @fold @transform(op: \"count\") @filter(op: \">\", value: [\"$no_references\"]) {{\nname @output(name: \"matched\")\n{name_filter}}}\n"
+        ));
+        query_args.insert(Arc::from("no_references".to_string()), FieldValue::Int64(0));
+    }
 
     query_str.push_str(&format!(
-        "name @output\n{name_filter}size @output\n{size_filter}created @output\n"
+        "id @output\n{id_filter}name @output\nreferences @fold {{\nname @output(name: \"references\")\n}}\nregistry @output\n{registry_filter}namespace @output\nrepo @output\ntag @output\nsize @output\n{size_filter}created @output\ndangling @output\nlabels @output\nparent @optional {{\nid @output(name: \"parent\")\n}}\n"
     ));
 
     query_str.push_str("}}");
-    let adapter = Arc::new(Adapter::new());
-
-    let vertices = execute_query(Adapter::schema(), adapter, &query_str, query_args).unwrap();
-    let mut images = vertices
-        .filter(|x| x["name"] != FieldValue::Null)
-        .map(|x| {
-            (
-                x["name"].as_str().unwrap().to_string(),
-                x["size"].as_u64().unwrap(),
-            )
+    Ok((query_str, query_args))
+}
+
+fn execute(
+    backend: Arc<dyn Backend>,
+    query_str: &str,
+    query_args: QueryArgs,
+) -> Result<Vec<output::Row>> {
+    let adapter = Arc::new(Adapter::new(backend));
+
+    let vertices = execute_query(Adapter::schema(), adapter.clone(), query_str, query_args)
+        .map_err(|e| Error::InvalidQuery(e.to_string()))?;
+    let rows = vertices.collect();
+    match adapter.take_error() {
+        Some(e) => Err(e),
+        None => Ok(rows),
+    }
+}
+
+fn run_query(backend: Arc<dyn Backend>, opts: &QueryOptions) -> Result<()> {
+    let query_str = match &opts.file {
+        Some(path) if path != Path::new("-") => std::fs::read_to_string(path)?,
+        _ => {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s)?;
+            s
+        }
+    };
+    let query_args = opts
+        .args
+        .iter()
+        .map(|(name, value)| (Arc::from(name.as_str()), value.clone()))
+        .collect();
+    let rows = execute(backend, &query_str, query_args)?;
+    output::print_rows(&rows, opts.format);
+    Ok(())
+}
+
+/// Untagged images have no name so are skipped unless asked for, in which case they're named
+/// by their short ID.
+fn find_images(
+    backend: Arc<dyn Backend>,
+    query_str: &str,
+    query_args: QueryArgs,
+    keep_untagged: bool,
+    untag: bool,
+) -> Result<Vec<output::ImageRecord>> {
+    let as_string = |v: &FieldValue| v.as_str().map(|s| s.to_string());
+    let as_strings = |v: &FieldValue| {
+        v.as_vec_with(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_default()
+    };
+    let images = execute(backend, query_str, query_args)?
+        .into_iter()
+        .filter(|x| keep_untagged || x["name"] != FieldValue::Null)
+        .map(|x| output::ImageRecord {
+            id: x["id"].as_str().unwrap().to_string(),
+            name: x["name"]
+                .as_str()
+                .or_else(|| x["id"].as_str().map(output::short_id))
+                .unwrap()
+                .to_string(),
+            registry: as_string(&x["registry"]),
+            namespace: as_string(&x["namespace"]),
+            repo: as_string(&x["repo"]),
+            tag: as_string(&x["tag"]),
+            size: x["size"].as_u64().unwrap(),
+            created: x["created"].as_str().unwrap().to_string(),
+            dangling: x["dangling"].as_bool().unwrap(),
+            labels: x["labels"]
+                .as_vec_with(|v| v.as_str().map(|s| s.to_string()))
+                .unwrap_or_default(),
+            parent: as_string(&x["parent"]),
+            references: as_strings(&x["references"]),
+            untag: untag.then(|| match x.get("matched") {
+                Some(matched) => as_strings(matched),
+                None => as_strings(&x["references"]),
+            }),
         })
-        .collect::<Vec<_>>();
-    let max_name_len = images
+        .collect();
+    Ok(images)
+}
+
+/// The most images passed to the runtime in one go.
+const MAX_BATCH: usize = 50;
+
+/// Removes the images, children before their parents, calling `report` with each outcome as
+/// soon as it's known. Protected images are skipped without asking the runtime.
+fn remove_images(
+    backend: &dyn Backend,
+    images: &[output::ImageRecord],
+    protection: &Protection,
+    jobs: usize,
+    mut report: impl FnMut(&output::Removal),
+) -> Vec<output::Removal> {
+    let mut removals = vec![];
+    for level in removal_levels(images) {
+        let remove = |references: &[&str]| {
+            let results = backend.remove_images(references);
+            results.into_iter().map(|r| r.map(|_| ())).collect()
+        };
+        removals.extend(remove_batches(
+            &level,
+            protection,
+            jobs,
+            remove,
+            &mut report,
+        ));
+    }
+    removals
+}
+
+/// Runtimes refuse to remove an image while others are built on it, so this groups the images
+/// such that each comes in an earlier group than its parent. The order within a group is kept.
+fn removal_levels(images: &[output::ImageRecord]) -> Vec<Vec<output::ImageRecord>> {
+    let index = images
         .iter()
-        .map(|(name, _)| name.len())
-        .max()
-        .unwrap_or_default();
+        .enumerate()
+        .map(|(i, image)| (image.id.as_str(), i))
+        .collect::<HashMap<_, _>>();
+    let parent_of = |i: usize| {
+        images[i]
+            .parent
+            .as_deref()
+            .and_then(|p| index.get(p).copied())
+    };
+    let mut height = vec![0; images.len()];
+    for i in 0..images.len() {
+        let mut parent = parent_of(i);
+        let mut distance = 1;
+        // The distance check stops a (broken) cycle of parents looping forever
+        while let Some(p) = parent
+            && distance <= images.len()
+        {
+            height[p] = height[p].max(distance);
+            parent = parent_of(p);
+            distance += 1;
+        }
+    }
+    let mut levels = vec![vec![]; height.iter().max().map_or(0, |h| h + 1)];
+    for (image, h) in images.iter().zip(height) {
+        levels[h].push(image.clone());
+    }
+    levels
+}
+
+/// Removes the items in batches, using up to `jobs` workers each handing a batch to `remove`
+/// at a time. `report` is called with each outcome in the same order as `items`, as soon as it
+/// and every item before it are done.
+fn remove_batches<T: Removable>(
+    items: &[T],
+    protection: &Protection,
+    jobs: usize,
+    remove: impl Fn(&[&str]) -> Vec<Result<()>> + Sync,
+    report: &mut dyn FnMut(&Removal<T>),
+) -> Vec<Removal<T>> {
+    let remove = |batch: &[T]| {
+        let references = batch
+            .iter()
+            .filter(|i| !protection.is_protected(*i))
+            .flat_map(|i| i.references())
+            .collect::<Vec<_>>();
+        let mut results = remove(&references).into_iter();
+        batch
+            .iter()
+            .map(|item| {
+                let protected = protection.is_protected(item);
+                // Every reference's result is taken even after one fails to keep them in step
+                let result = if protected {
+                    Ok(())
+                } else {
+                    item.references()
+                        .iter()
+                        .map(|reference| {
+                            results.next().unwrap_or_else(|| {
+                                Err(Error::Runtime(format!(
+                                    "the runtime gave no result for {reference}"
+                                )))
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                        .into_iter()
+                        .collect::<Result<()>>()
+                };
+                Removal {
+                    item: item.clone(),
+                    removed: !protected && result.is_ok(),
+                    protected,
+                    error: result.err().map(|e| e.to_string()),
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Split evenly between the workers so none sit idle, unless that makes huge batches
+    let jobs = jobs.max(1);
+    let batch_size = items.len().div_ceil(jobs).clamp(1, MAX_BATCH);
+    let batches = items.chunks(batch_size).collect::<Vec<_>>();
+    let next = AtomicUsize::new(0);
+    let mut removals: Vec<Option<Vec<Removal<T>>>> = vec![None; batches.len()];
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..jobs.min(batches.len()) {
+            let tx = tx.clone();
+            let (next, remove, batches) = (&next, &remove, &batches);
+            s.spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(batch) = batches.get(i) else {
+                        break;
+                    };
+                    if tx.send((i, remove(batch))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+        let mut reported = 0;
+        for (i, batch) in rx {
+            removals[i] = Some(batch);
+            while let Some(Some(batch)) = removals.get(reported) {
+                batch.iter().for_each(&mut *report);
+                reported += 1;
+            }
+        }
+    });
+    removals.into_iter().flatten().flatten().collect()
+}
+
+fn size_summary(
+    backend: Arc<dyn Backend>,
+    images: &[output::ImageRecord],
+) -> Result<output::SizeSummary> {
+    let usage = layer_usage(backend, images)?;
+    let ids = images.iter().map(|i| i.id.as_str()).collect::<Vec<_>>();
+    Ok(output::SizeSummary {
+        images: images.len(),
+        size: images.iter().map(|i| i.size).sum(),
+        reclaimable: usage.reclaimable(&ids),
+        usage,
+    })
+}
 
+/// The layers of the images and those the other images use, removing the images frees the
+/// layers no other image uses. Adding up the image sizes instead counts a shared layer once for
+/// every image using it. Layer sizes are only looked up for these images, the others just need
+/// their layer digests.
+fn layer_usage(backend: Arc<dyn Backend>, images: &[output::ImageRecord]) -> Result<LayerUsage> {
+    if images.is_empty() {
+        return Ok(LayerUsage::default());
+    }
+    let removed_query = r#"{
+      Image {
+        id @filter(op: "one_of", value: ["$ids"]) @output
+        size @output
+        unique_size @output
+        layers @fold {
+          digest @output(name: "layers")
+          size @output(name: "layer_sizes")
+        }
+      }
+    }"#;
+    let kept_query = r#"{
+      Image {
+        id @filter(op: "not_one_of", value: ["$ids"])
+        layer_digests @output
+      }
+    }"#;
+    let ids = images.iter().map(|i| i.id.as_str()).collect::<Vec<_>>();
+    let query_args = BTreeMap::from([(Arc::from("ids"), ids.into())]);
+    let as_strings = |v: &FieldValue| {
+        v.as_vec_with(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_default()
+    };
+    let mut usage = LayerUsage {
+        kept: execute(backend.clone(), kept_query, query_args.clone())?
+            .iter()
+            .flat_map(|row| as_strings(&row["layer_digests"]))
+            .collect(),
+        ..Default::default()
+    };
+    // Without layers the best guess is what the runtime says is unique to the image, which
+    // misses layers only shared with other images being removed. Failing that assume nothing
+    // is shared.
+    for row in execute(backend, removed_query, query_args)? {
+        let id = row["id"].as_str().unwrap().to_string();
+        let digests = as_strings(&row["layers"]);
+        let sizes = row["layer_sizes"]
+            .as_vec_with(FieldValue::as_u64)
+            .unwrap_or_default();
+        if digests.is_empty() {
+            let size = row["unique_size"]
+                .as_u64()
+                .or_else(|| row["size"].as_u64())
+                .unwrap();
+            usage.unlayered.insert(id, size);
+        } else {
+            usage
+                .layers
+                .insert(id, digests.into_iter().zip(sizes).collect());
+        }
+    }
+    Ok(usage)
+}
+
+/// Runs the query for the filters returning the matching images, largest first if sorting.
+fn select_images(
+    backend: Arc<dyn Backend>,
+    filter: &FilterOptions,
+) -> Result<Vec<output::ImageRecord>> {
+    let (query_str, query_args) = build_query(filter)?;
+    let untagged = filter.include_untagged || filter.only_untagged;
+    let mut images = find_images(backend, &query_str, query_args, untagged, filter.untag)?;
     if filter.sort {
-        images.sort_by(|(_, a), (_, b)| b.cmp(a));
+        images.sort_by_key(|i| std::cmp::Reverse(i.size));
     }
+    Ok(images)
+}
 
-    match args.command {
-        Commands::Print(_) => {
-            println!("{query_str}");
-        }
-        Commands::Ls(_) => {
-            list_images(images, max_name_len);
+fn apply_policy(backend: Arc<dyn Backend>, opts: &ApplyOptions) -> Result<()> {
+    let policy = Policy::load(&opts.policy)?;
+    let mut protection = Protection::from_options(&opts.protect)?;
+    if !opts.protect.force_protected {
+        protection.patterns.extend(policy.protect.patterns);
+        protection.labels.extend(policy.protect.labels);
+    }
+    let mut records = vec![];
+    for rule in &policy.rules {
+        let images = select_images(backend.clone(), &rule.filter())?;
+        if opts.format == OutputFormat::Table {
+            let size = images.iter().map(|i| i.size).sum();
+            println!(
+                "Rule '{}' ({}): {} images totalling {}",
+                rule.name,
+                rule.action,
+                images.len(),
+                output::human_size(size)
+            );
         }
-        Commands::Size(_) => {
-            let s: usize = images.iter().map(|(_, s)| *s as usize).sum();
-            let human_size = SpecificSize::new(s as f64, Byte).unwrap();
-            let size = if s > 1_000_000_000 {
-                let s: SpecificSize<Gigabyte> = human_size.into();
-                s.to_string()
+        if rule.action == Action::Rm && !opts.dry_run {
+            let (protected, removable): (Vec<_>, Vec<_>) =
+                images.into_iter().partition(|i| protection.is_protected(i));
+            let freeing = removable
+                .iter()
+                .filter(|i| i.frees_space())
+                .cloned()
+                .collect::<Vec<_>>();
+            let planned = size_summary(backend.clone(), &freeing)?;
+            let removable = if opts.yes {
+                removable
+            } else if std::io::stdin().is_terminal() {
+                confirm::confirm(
+                    removable,
+                    planned.reclaimable(),
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stderr(),
+                )?
             } else {
-                let s: SpecificSize<Megabyte> = human_size.into();
-                s.to_string()
+                return Err(Error::ConfirmationRequired(Kind::Image));
             };
-            println!("{} images totalling {}", images.len(), size);
-        }
-        Commands::Rm(_) => {
-            if filter.dry_run {
-                list_images(images, max_name_len);
-            } else {
-                for (image, _) in &images {
-                    println!("Removing: {}", image);
-                    let o = Command::new("docker")
-                        .args(["image", "rm"])
-                        .arg(image)
-                        .output()
-                        .unwrap();
-                    if !o.status.success() {
-                        println!("{}", String::from_utf8_lossy(&o.stderr));
+            let images = protected.into_iter().chain(removable).collect::<Vec<_>>();
+            let removals = remove_images(
+                backend.as_ref(),
+                &images,
+                &protection,
+                opts.jobs,
+                |removal| {
+                    if opts.format == OutputFormat::Table {
+                        output::print_removal(removal, opts.format);
                     }
-                }
+                },
+            );
+            if opts.format == OutputFormat::Table {
+                let summary = output::RemovalSummary::with_summary(&removals, &planned);
+                println!("{summary}");
+            }
+            records.extend(removals.into_iter().map(|r| output::RuleRecord {
+                rule: rule.name.clone(),
+                action: rule.action,
+                image: r.item,
+                removed: Some(r.removed),
+                protected: r.protected,
+                error: r.error,
+            }));
+        } else {
+            if opts.format == OutputFormat::Table {
+                list(&images, opts.format);
             }
+            records.extend(images.into_iter().map(|image| output::RuleRecord {
+                rule: rule.name.clone(),
+                action: rule.action,
+                protected: protection.is_protected(&image),
+                image,
+                removed: None,
+                error: None,
+            }));
         }
     }
+    if opts.format != OutputFormat::Table {
+        output::print_records(&records, opts.format);
+    }
+    Ok(())
 }
 
-fn list_images(images: Vec<(String, u64)>, max_name_len: usize) {
-    for (image, size) in &images {
-        let human_size = SpecificSize::new(*size as f64, Byte).unwrap();
-        let size = if *size > 1_000_000_000 {
-            let s: SpecificSize<Gigabyte> = human_size.into();
-            s.to_string()
-        } else {
-            let s: SpecificSize<Megabyte> = human_size.into();
-            s.to_string()
-        };
-        let padding = " ".repeat(max_name_len - image.len());
-        println!("{}{}\t{}", image, padding, size);
+fn list<T: Removable>(items: &[T], format: OutputFormat) {
+    if format != OutputFormat::Table {
+        output::print_records(items, format);
+        return;
+    }
+    let names = items
+        .iter()
+        .map(|i| i.names().join(", "))
+        .collect::<Vec<_>>();
+    let max_name_len = names.iter().map(|n| n.len()).max().unwrap_or_default();
+    for (item, name) in items.iter().zip(&names) {
+        let padding = " ".repeat(max_name_len - name.len());
+        println!(
+            "{}{}\t{}",
+            name,
+            padding,
+            output::size_or_unknown(item.size())
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use backend::Fixture;
+
+    fn image(hash: &str, repository: &str, tag: &str, size: usize, created_at: &str) -> Image {
+        Image {
+            hash: hash.to_string(),
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            size,
+            created_at: created_at.parse().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn fixture() -> Fixture {
+        let images = vec![
+            image(
+                "e9d2252ab371",
+                "docker.io/library/ubuntu",
+                "24.10",
+                82_756_709,
+                "2025-06-20T10:17:16Z",
+            ),
+            image(
+                "71eaf13299f4",
+                "ubuntu",
+                "18.04",
+                63_100_000,
+                "2022-10-25T01:53:28Z",
+            ),
+            Image {
+                labels: [("team".to_string(), "work".to_string())].into(),
+                ..image(
+                    "9a8b7c6d5e4f",
+                    "work/app",
+                    "1",
+                    1_200_000_000,
+                    "2025-07-02T09:00:00Z",
+                )
+            },
+            image(
+                "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0",
+                "",
+                "",
+                5_000_000,
+                "2024-03-01T12:00:00Z",
+            ),
+        ];
+        let containers = vec![Container {
+            id: "3f4e0c1d2b6a".to_string(),
+            name: "happy_turing".to_string(),
+            image: "docker.io/library/ubuntu:24.10".to_string(),
+            image_id: None,
+            state: "exited".to_string(),
+            status: "Exited (0) 2 days ago".to_string(),
+            created_at: "2025-07-20T09:01:02Z".parse().unwrap(),
+            networks: vec!["app_default".to_string()],
+        }];
+        Fixture::new(images, containers)
+    }
+
+    fn cli(args: &[&str]) -> args::Cli {
+        args::Cli::parse_from(std::iter::once("docker-cleanup").chain(args.iter().copied()))
+    }
+
+    /// What the command line selects of a kind other than images.
+    fn selected<T: Record>(backend: Arc<dyn Backend>, args: &[&str]) -> Result<Vec<T>> {
+        select::<T>(backend, cli(args).command.filter().unwrap())
+    }
+
+    /// The names of what the command line selects.
+    fn selected_names<T: Record>(backend: Arc<dyn Backend>, args: &[&str]) -> Vec<String> {
+        selected::<T>(backend, args)
+            .unwrap()
+            .iter()
+            .map(|r| r.name().to_string())
+            .collect()
+    }
+
+    /// Removes the records one batch at a time like `rm` does.
+    fn remove_records<T: Record>(
+        backend: &dyn Backend,
+        records: &[T],
+        protection: &Protection,
+    ) -> Vec<Removal<T>> {
+        let remove = |references: &[&str]| T::remove(backend, references);
+        remove_batches(records, protection, 1, remove, &mut |_| {})
+    }
 
     #[test]
     fn can_deserialize_podman_image() {
@@ -202,11 +1196,85 @@ mod tests {
         let _image: docker::Image = serde_json::from_str(json).unwrap();
 
         let image: ImageOutput = serde_json::from_str(json).unwrap();
+        assert!(matches!(image, ImageOutput::Docker(_)));
+    }
+
+    #[test]
+    fn can_deserialize_containers() {
+        let docker = r#"{"Command":"\"bash\"","CreatedAt":"2025-07-20 10:01:02 +0100 BST","ID":"3f4e0c1d2b6a","Image":"ubuntu:24.10","Labels":"","LocalVolumes":"0","Mounts":"","Names":"happy_turing","Networks":"bridge","Ports":"","RunningFor":"2 days ago","Size":"0B","State":"exited","Status":"Exited (0) 2 days ago"}"#;
+        let container: ContainerOutput = serde_json::from_str(docker).unwrap();
+        assert!(matches!(container, ContainerOutput::Docker(_)));
+
+        let podman = r#"
+            {
+                "AutoRemove": false,
+                "Command": ["bash"],
+                "Created": 1753002062,
+                "CreatedAt": "2 days ago",
+                "Exited": true,
+                "ExitedAt": 1753002070,
+                "ExitCode": 0,
+                "Id": "3f4e0c1d2b6a9c8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e",
+                "Image": "docker.io/library/ubuntu:24.10",
+                "ImageID": "e9d2252ab371a1149d3ef64b7793a274375dee5d9ec61b9e4fb41d75f156c1a1",
+                "IsInfra": false,
+                "Labels": null,
+                "Mounts": [],
+                "Names": ["happy_turing"],
+                "Pid": 0,
+                "Pod": "",
+                "Ports": null,
+                "State": "exited",
+                "Status": ""
+            }"#;
+        let container: ContainerOutput = serde_json::from_str(podman).unwrap();
+        assert!(matches!(container, ContainerOutput::Podman(_)));
+        let container: Container = container.into();
+        assert_eq!(container.name, "happy_turing");
+
+        let docker = docker.replace(
+            r#""Networks":"bridge""#,
+            r#""Networks":"bridge,app_default""#,
+        );
+        let container: Container = serde_json::from_str::<ContainerOutput>(&docker)
+            .unwrap()
+            .into();
+        assert_eq!(container.networks, ["bridge", "app_default"]);
+    }
+
+    #[test]
+    fn containers_match_images() {
+        let image = Image {
+            hash: "e9d2252ab371a1149d3ef64b7793a274375dee5d9ec61b9e4fb41d75f156c1a1".to_string(),
+            repository: "ubuntu".to_string(),
+            tag: "latest".to_string(),
+            size: 0,
+            created_at: jiff::Timestamp::UNIX_EPOCH,
+            ..Default::default()
+        };
+        let mut container = Container {
+            id: "3f4e0c1d2b6a".to_string(),
+            name: "happy_turing".to_string(),
+            image: "ubuntu".to_string(),
+            image_id: None,
+            state: "exited".to_string(),
+            status: String::new(),
+            created_at: jiff::Timestamp::UNIX_EPOCH,
+            networks: vec![],
+        };
+        assert!(container.uses_image(&image));
+        container.image = "ubuntu:24.10".to_string();
+        assert!(!container.uses_image(&image));
+        container.image = "e9d2252ab371".to_string();
+        assert!(container.uses_image(&image));
+        container.image = "ubuntu:24.10".to_string();
+        container.image_id = Some(format!("sha256:{}", image.hash));
+        assert!(container.uses_image(&image));
     }
 
     #[test]
     fn get_june_images() {
-        let query = r#"{
+        let nested_query = r#"{
           Image {
             created_after(timestamp: "2025-06-01 00:00:00+00") {
               created_before(timestamp: "2025-07-01 00:00:00+00") {
@@ -229,13 +1297,1084 @@ mod tests {
           }
         }"#;
 
-        let adapter = Arc::new(Adapter::new());
-        let args: BTreeMap<Arc<str>, trustfall::FieldValue> = BTreeMap::new();
+        for query in [nested_query, query] {
+            let adapter = Arc::new(Adapter::new(Arc::new(fixture())));
+            let args: QueryArgs = BTreeMap::new();
 
-        let vertices = execute_query(Adapter::schema(), adapter, query, args).unwrap();
-        println!("Printing vertices");
-        for v in vertices {
-            println!("{:?}", v);
+            let vertices = execute_query(Adapter::schema(), adapter, query, args).unwrap();
+            let vertices = vertices.collect::<Vec<_>>();
+            assert_eq!(vertices.len(), 1);
+            assert_eq!(vertices[0]["tag"], FieldValue::from("24.10"));
         }
     }
+
+    #[test]
+    fn query_with_arguments() {
+        let query = r#"{
+          Image {
+            name @output
+            size @filter(op: ">", value: ["$size"])
+            used_by_containers @fold {
+              container: name @output
+            }
+          }
+        }"#;
+        let args = cli(&["query", "--arg", "size=70000000"]);
+        let Commands::Query(opts) = args.command else {
+            panic!("not a query");
+        };
+        let query_args = opts
+            .args
+            .into_iter()
+            .map(|(name, value)| (Arc::from(name), value))
+            .collect();
+        let rows = execute(Arc::new(fixture()), query, query_args).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0]["container"],
+            FieldValue::List(vec![FieldValue::from("happy_turing")].into())
+        );
+        assert_eq!(rows[1]["container"], FieldValue::List(vec![].into()));
+    }
+
+    #[test]
+    fn query_errors_have_their_own_exit_codes() {
+        let backend: Arc<dyn Backend> = Arc::new(fixture());
+        let bad_timestamp = r#"{
+          Image {
+            created_before(timestamp: "last tuesday") {
+              name @output
+            }
+          }
+        }"#;
+        let err = execute(backend.clone(), bad_timestamp, BTreeMap::new()).unwrap_err();
+        assert!(matches!(err, Error::InvalidTimestamp { .. }));
+        assert_eq!(err.exit_code(), 7);
+
+        let err = execute(backend, "{Image{colour @output}}", BTreeMap::new()).unwrap_err();
+        assert!(matches!(err, Error::InvalidQuery(_)));
+        assert_eq!(err.exit_code(), 6);
+
+        let err = Error::ConfirmationRequired(Kind::BuildCache);
+        assert!(
+            err.to_string()
+                .starts_with("refusing to remove build cache records")
+        );
+        assert_eq!(err.exit_code(), 9);
+    }
+
+    #[test]
+    fn filter_on_labels_ids_and_dangling() {
+        let names = |args: &[&str]| {
+            let args = cli(args);
+            let filter = args.command.filter().unwrap();
+            let (query_str, query_args) = build_query(filter).unwrap();
+            let untagged = filter.include_untagged || filter.only_untagged;
+            find_images(Arc::new(fixture()), &query_str, query_args, untagged, false)
+                .unwrap()
+                .into_iter()
+                .map(|i| i.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&["ls", "--label", "team=work"]), ["work/app:1"]);
+        assert!(names(&["ls", "--label", "team=work", "--label", "a=b"]).is_empty());
+        assert_eq!(
+            names(&["ls", "--id-prefix", "sha256:71ea"]),
+            ["ubuntu:18.04"]
+        );
+        assert_eq!(names(&["ls", "--dangling"]), ["0f1e2d3c4b5a"]);
+        assert_eq!(names(&["ls", "--only-untagged"]), ["0f1e2d3c4b5a"]);
+        assert_eq!(names(&["ls", "--include-untagged"]).len(), 4);
+        assert_eq!(names(&["ls"]).len(), 3);
+    }
+
+    #[test]
+    fn reference_tags_default_to_latest() {
+        let backend = Arc::new(Fixture::new(
+            vec![Image {
+                references: vec![
+                    "localhost:5000/app".to_string(),
+                    "localhost:5000/app:1.2".to_string(),
+                ],
+                ..image(
+                    "a1",
+                    "localhost:5000/app",
+                    "latest",
+                    1,
+                    "2024-01-01T00:00:00Z",
+                )
+            }],
+            vec![],
+        ));
+        let query = r#"{
+          Image {
+            tag @output
+            references {
+              name @output(name: "reference")
+              tag @output(name: "reference_tag")
+            }
+          }
+        }"#;
+        let rows = execute(backend, query, BTreeMap::new()).unwrap();
+        let tags = rows
+            .iter()
+            .map(|row| (row["tag"].clone(), row["reference_tag"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            [
+                ("latest".into(), "latest".into()),
+                ("latest".into(), "1.2".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn untagged_images_go_by_id() {
+        let backend = Arc::new(fixture());
+        let id = "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0";
+        let images =
+            select_images(backend.clone(), cli(&["rm"]).command.filter().unwrap()).unwrap();
+        assert!(images.iter().all(|i| !i.dangling));
+
+        let args = cli(&["rm", "--include-untagged"]);
+        let images = select_images(backend.clone(), args.command.filter().unwrap()).unwrap();
+        assert_eq!(images.len(), 4);
+        let untagged = images.iter().find(|i| i.dangling).unwrap().clone();
+        // Listed by the short ID, and removed by the full one
+        assert_eq!(untagged.names(), ["0f1e2d3c4b5a"]);
+        assert_eq!(untagged.references(), [id]);
+        let removals = remove_images(
+            backend.as_ref(),
+            &[untagged],
+            &Protection::default(),
+            1,
+            |_| {},
+        );
+        assert!(removals[0].removed);
+        assert_eq!(backend.removed(), [id]);
+    }
+
+    #[test]
+    fn relative_ages() {
+        let now: Zoned = "2025-07-20T12:00:00+00:00[UTC]".parse().unwrap();
+        let args = cli(&["ls", "--older-than", "30d", "--newer-than", "P1Y"]);
+        let filter = args.command.filter().unwrap();
+        assert_eq!(
+            filter.resolve_created_before(&now),
+            Some("2025-06-20T12:00:00Z".parse().unwrap())
+        );
+        assert_eq!(
+            filter.resolve_created_after(&now),
+            Some("2024-07-20T12:00:00Z".parse().unwrap())
+        );
+
+        let args = cli(&[
+            "ls",
+            "--older-than",
+            "2w",
+            "--created-before",
+            "2025-07-10T00:00:00Z",
+            "--newer-than",
+            "12h",
+        ]);
+        let filter = args.command.filter().unwrap();
+        assert_eq!(
+            filter.resolve_created_before(&now),
+            Some("2025-07-06T12:00:00Z".parse().unwrap())
+        );
+        assert_eq!(
+            filter.resolve_created_after(&now),
+            Some("2025-07-20T00:00:00Z".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn children_are_removed_before_parents() {
+        let child_of = |mut image: Image, parent: &str| {
+            image.parent = Some(parent.to_string());
+            image
+        };
+        let backend = Arc::new(Fixture::new(
+            vec![
+                image("a1", "base", "1", 300, "2024-01-01T00:00:00Z"),
+                child_of(image("a2", "app", "1", 200, "2024-02-01T00:00:00Z"), "a1"),
+                child_of(image("a3", "app", "2", 100, "2024-03-01T00:00:00Z"), "a2"),
+                image("b1", "other", "1", 50, "2024-01-01T00:00:00Z"),
+            ],
+            vec![],
+        ));
+
+        let query = r#"{
+          Image {
+            name @output
+            parent @optional {
+              name @output(name: "parent")
+            }
+            children @fold {
+              name @output(name: "children")
+            }
+          }
+        }"#;
+        let rows = execute(backend.clone(), query, BTreeMap::new()).unwrap();
+        assert_eq!(rows[1]["name"], "app:1".into());
+        assert_eq!(rows[1]["parent"], "base:1".into());
+        assert_eq!(
+            rows[1]["children"],
+            FieldValue::List(vec!["app:2".into()].into())
+        );
+        assert_eq!(rows[3]["parent"], FieldValue::Null);
+
+        // Largest first puts every parent before its children
+        let images = select_images(
+            backend.clone(),
+            cli(&["rm", "--sort"]).command.filter().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(images[1].parent.as_deref(), Some("a1"));
+        let removals = remove_images(backend.as_ref(), &images, &Protection::default(), 4, |_| {});
+        assert!(removals.iter().all(|r| r.removed));
+        assert_eq!(backend.removed(), ["app:2", "other:1", "app:1", "base:1"]);
+    }
+
+    /// Two versions of an app built on the same base layer, and the base image itself.
+    fn layered_fixture() -> Arc<Fixture> {
+        Arc::new(
+            Fixture::new(
+                vec![
+                    image("a1", "app", "1", 150, "2024-01-01T00:00:00Z"),
+                    image("a2", "app", "2", 130, "2024-02-01T00:00:00Z"),
+                    image("b1", "base", "1", 110, "2024-01-01T00:00:00Z"),
+                ],
+                vec![],
+            )
+            .with_layers("a1", &[("sha256:base", 100), ("sha256:one", 50)])
+            .with_layers("a2", &[("sha256:base", 100), ("sha256:two", 30)])
+            .with_layers("b1", &[("sha256:base", 100), ("sha256:b", 10)]),
+        )
+    }
+
+    #[test]
+    fn layers_can_be_queried() {
+        let query = r#"{
+          Image {
+            name @output
+            layers {
+              digest @filter(op: "=", value: ["$digest"])
+              images @fold {
+                name @output(name: "sharing")
+              }
+            }
+          }
+        }"#;
+        let args = BTreeMap::from([(Arc::from("digest"), "sha256:two".into())]);
+        let rows = execute(layered_fixture(), query, args).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0]["sharing"],
+            FieldValue::List(vec!["app:2".into()].into())
+        );
+    }
+
+    #[test]
+    fn layers_are_counted_once() {
+        let backend = layered_fixture();
+        let filter = cli(&["size", "--name-contains", "app"]);
+        let images = select_images(backend.clone(), filter.command.filter().unwrap()).unwrap();
+        assert_eq!(images.iter().map(|i| i.size).sum::<u64>(), 280);
+        // The base layer stays for base:1, whose layer sizes aren't needed
+        let summary = size_summary(backend.clone(), &images).unwrap();
+        assert_eq!(summary.reclaimable, 80);
+        let mut looked_up = backend.layer_lookups();
+        looked_up.sort();
+        assert_eq!(looked_up, ["a1", "a2"]);
+
+        let images =
+            select_images(backend.clone(), cli(&["size"]).command.filter().unwrap()).unwrap();
+        assert_eq!(size_summary(backend, &images).unwrap().reclaimable, 190);
+    }
+
+    #[test]
+    fn freed_leaves_layers_kept_images_use() {
+        let backend = layered_fixture();
+        let filter = cli(&["rm", "--name-contains", "app"]);
+        let images = select_images(backend.clone(), filter.command.filter().unwrap()).unwrap();
+        let summary = size_summary(backend.clone(), &images).unwrap();
+        // Removing just one of them leaves the base layer for the other
+        let picked = images
+            .iter()
+            .filter(|i| i.id == "a1")
+            .cloned()
+            .collect::<Vec<_>>();
+        let removals = remove_images(backend.as_ref(), &picked, &Protection::default(), 1, |_| {});
+        assert_eq!(
+            output::RemovalSummary::with_summary(&removals, &summary).freed,
+            50
+        );
+    }
+
+    #[test]
+    fn dry_run_compares_total_and_reclaimable_size() {
+        let backend = Arc::new(
+            Fixture::new(
+                vec![
+                    image("a1", "app", "1", 150_000_000, "2024-01-01T00:00:00Z"),
+                    image("a2", "app", "2", 130_000_000, "2024-02-01T00:00:00Z"),
+                    image("b1", "base", "1", 110_000_000, "2024-01-01T00:00:00Z"),
+                ],
+                vec![],
+            )
+            .with_layers(
+                "a1",
+                &[("sha256:base", 100_000_000), ("sha256:one", 50_000_000)],
+            )
+            .with_layers(
+                "a2",
+                &[("sha256:base", 100_000_000), ("sha256:two", 30_000_000)],
+            )
+            .with_layers(
+                "b1",
+                &[("sha256:base", 100_000_000), ("sha256:b", 10_000_000)],
+            ),
+        );
+        let args = cli(&["rm", "--dry-run", "--name-contains", "app"]);
+        let images = select_images(backend.clone(), args.command.filter().unwrap()).unwrap();
+        let summary = size_summary(backend, &images).unwrap();
+        assert_eq!(
+            summary.to_string(),
+            "2 images totalling 280 MB, removing them would free 80 MB"
+        );
+    }
+
+    #[test]
+    fn failed_layer_lookups_are_reported() {
+        let backend = Arc::new(
+            Fixture::new(
+                vec![image("a1", "app", "1", 150, "2024-01-01T00:00:00Z")],
+                vec![],
+            )
+            .with_broken_layers("a1"),
+        );
+        let images =
+            select_images(backend.clone(), cli(&["size"]).command.filter().unwrap()).unwrap();
+        let error = size_summary(backend, &images).unwrap_err();
+        assert!(error.to_string().contains("sizes are unknown"), "{error}");
+    }
+
+    #[test]
+    fn unique_size_is_used_without_layers() {
+        // Or failing that the whole size
+        let shared = |mut image: Image, shared_size| {
+            image.shared_size = Some(shared_size);
+            image
+        };
+        let backend = Arc::new(Fixture::new(
+            vec![
+                shared(image("c1", "app", "1", 150, "2024-01-01T00:00:00Z"), 100),
+                image("c2", "app", "2", 130, "2024-02-01T00:00:00Z"),
+            ],
+            vec![],
+        ));
+        let images =
+            select_images(backend.clone(), cli(&["size"]).command.filter().unwrap()).unwrap();
+        assert_eq!(size_summary(backend, &images).unwrap().reclaimable, 180);
+    }
+
+    #[test]
+    fn keep_latest_per_repository() {
+        let backend = Arc::new(Fixture::new(
+            vec![
+                image("a1", "ubuntu", "18.04", 1, "2022-10-25T01:53:28Z"),
+                image("a2", "ubuntu", "24.10", 1, "2025-06-20T10:17:16Z"),
+                image("a3", "ubuntu", "22.04", 1, "2024-01-10T00:00:00Z"),
+                image("b1", "work/app", "1", 1, "2023-01-01T00:00:00Z"),
+            ],
+            vec![],
+        ));
+        let args = cli(&["ls", "--keep-latest", "1"]);
+        let (query_str, query_args) = build_query(args.command.filter().unwrap()).unwrap();
+        let mut names = find_images(backend.clone(), &query_str, query_args, false, false)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["ubuntu:18.04", "ubuntu:22.04"]);
+
+        let query = "{Image{tag @output rank_in_repo @output}}";
+        let rows = execute(backend, query, BTreeMap::new()).unwrap();
+        let ranks = rows
+            .iter()
+            .map(|r| {
+                (
+                    r["tag"].as_str().unwrap(),
+                    r["rank_in_repo"].as_u64().unwrap(),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(ranks["24.10"], 1);
+        assert_eq!(ranks["22.04"], 2);
+        assert_eq!(ranks["18.04"], 3);
+        assert_eq!(ranks["1"], 1);
+
+        // However the runtime writes the name, it's the same repository
+        let backend = Arc::new(Fixture::new(
+            vec![
+                image("a1", "ubuntu", "18.04", 1, "2022-10-25T01:53:28Z"),
+                image(
+                    "a2",
+                    "docker.io/library/ubuntu",
+                    "24.10",
+                    1,
+                    "2025-06-20T10:17:16Z",
+                ),
+                image("a3", "library/ubuntu", "22.04", 1, "2024-01-10T00:00:00Z"),
+            ],
+            vec![],
+        ));
+        let query = "{Image{tag @output rank_in_repo @output}}";
+        let rows = execute(backend, query, BTreeMap::new()).unwrap();
+        let mut ranks = rows
+            .iter()
+            .map(|r| r["rank_in_repo"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        ranks.sort();
+        assert_eq!(ranks, [1, 2, 3]);
+    }
+
+    #[test]
+    fn policy_rules_run_in_order() {
+        let policy = Policy::parse(
+            r#"
+            [[rule]]
+            name = "big-work-images"
+            name_contains = "work"
+            larger_than = "1GB"
+            action = "rm"
+
+            [[rule]]
+            name = "old-unused"
+            older_than = "30d"
+            unused = true
+            protected_labels = ["team=work"]
+            action = "rm"
+
+            [[rule]]
+            name = "report"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.rules.len(), 3);
+        assert_eq!(policy.rules[2].action, Action::Ls);
+
+        let backend = Arc::new(fixture());
+        let first = select_images(backend.clone(), &policy.rules[0].filter()).unwrap();
+        remove_images(backend.as_ref(), &first, &Protection::default(), 1, |_| {});
+        let second = select_images(backend.clone(), &policy.rules[1].filter()).unwrap();
+        remove_images(backend.as_ref(), &second, &Protection::default(), 1, |_| {});
+        assert_eq!(backend.removed(), ["work/app:1", "ubuntu:18.04"]);
+
+        let protected = Policy::parse(
+            r#"
+            [[rule]]
+            name = "protected"
+            protected_labels = ["team=work"]
+            name_contains = "work"
+            "#,
+        )
+        .unwrap();
+        let backend = Arc::new(fixture());
+        assert!(
+            select_images(backend, &protected.rules[0].filter())
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(Policy::parse("[[rule]]\nname = \"typo\"\nolder_then = \"1d\"").is_err());
+    }
+
+    #[test]
+    fn protected_images_are_never_removed() {
+        let mut protection = Protection {
+            patterns: vec!["ubuntu:*".to_string()],
+            labels: vec![],
+        };
+        let backend = Arc::new(fixture());
+        let images =
+            select_images(backend.clone(), cli(&["rm"]).command.filter().unwrap()).unwrap();
+        let removals = remove_images(backend.as_ref(), &images, &protection, 1, |_| {});
+        assert_eq!(backend.removed(), ["work/app:1"]);
+        let skipped = removals
+            .iter()
+            .find(|r| r.item.name == "ubuntu:18.04")
+            .unwrap();
+        assert!(skipped.protected && !skipped.removed && skipped.error.is_none());
+
+        let backend = Arc::new(fixture());
+        protection.labels.push("team=work".to_string());
+        protection.patterns.clear();
+        let images =
+            select_images(backend.clone(), cli(&["rm"]).command.filter().unwrap()).unwrap();
+        remove_images(backend.as_ref(), &images, &protection, 1, |_| {});
+        assert_eq!(backend.removed(), ["ubuntu:18.04"]);
+
+        let args = cli(&["rm", "--protect", "*", "--force-protected"]);
+        let protection = Protection::from_options(&args.command.filter().unwrap().protect);
+        assert_eq!(protection.unwrap(), Protection::default());
+    }
+
+    #[test]
+    fn rm_skips_images_with_containers() {
+        let args = cli(&["rm", "--unused"]);
+        let backend = Arc::new(fixture());
+        let (query_str, query_args) = build_query(args.command.filter().unwrap()).unwrap();
+        let images = find_images(backend.clone(), &query_str, query_args, false, false).unwrap();
+        let removals = remove_images(backend.as_ref(), &images, &Protection::default(), 1, |_| {});
+        assert!(removals.iter().all(|r| r.removed));
+        assert_eq!(backend.removed(), ["ubuntu:18.04", "work/app:1"]);
+
+        let args = cli(&["rm", "--name-contains", "ubuntu"]);
+        let (query_str, query_args) = build_query(args.command.filter().unwrap()).unwrap();
+        let images = find_images(backend.clone(), &query_str, query_args, false, false).unwrap();
+        let removals = remove_images(backend.as_ref(), &images, &Protection::default(), 1, |_| {});
+        assert_eq!(removals.len(), 1);
+        assert!(!removals[0].removed);
+        assert!(removals[0].error.as_ref().unwrap().contains("is using"));
+        assert_eq!(backend.removed(), ["ubuntu:18.04", "work/app:1"]);
+        assert_eq!(backend.images().unwrap().len(), 2);
+
+        let args = cli(&["rm", "--only-untagged"]);
+        let (query_str, query_args) = build_query(args.command.filter().unwrap()).unwrap();
+        let images = find_images(backend.clone(), &query_str, query_args, true, false).unwrap();
+        remove_images(backend.as_ref(), &images, &Protection::default(), 1, |_| {});
+        assert_eq!(
+            backend.removed().last().unwrap(),
+            "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0"
+        );
+    }
+
+    #[test]
+    fn parallel_removal_reports_in_order() {
+        let backend = Arc::new(fixture());
+        let args = cli(&["rm", "--include-untagged", "--sort", "--jobs", "4"]);
+        let filter = args.command.filter().unwrap();
+        assert_eq!(filter.jobs, 4);
+        let images = select_images(backend.clone(), filter).unwrap();
+        let mut reported = vec![];
+        let removals = remove_images(
+            backend.as_ref(),
+            &images,
+            &Protection::default(),
+            filter.jobs,
+            |r| reported.push(r.item.name.clone()),
+        );
+        let names = images.iter().map(|i| i.name.clone()).collect::<Vec<_>>();
+        assert_eq!(reported, names);
+        assert_eq!(
+            removals.iter().map(|r| &r.item.name).collect::<Vec<_>>(),
+            names.iter().collect::<Vec<_>>()
+        );
+
+        let summary = output::RemovalSummary::of(&removals);
+        assert_eq!(summary.removed, 3);
+        // The ubuntu image is still used by a container
+        assert_eq!(summary.failed, 1);
+        assert_eq!(
+            summary.freed,
+            images
+                .iter()
+                .filter(|i| !i.name.contains("24.10"))
+                .map(|i| i.size)
+                .sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn volumes_use_the_same_filters() {
+        let volume = |name: &str, created: &str, size: Option<u64>, in_use: bool| Volume {
+            name: name.to_string(),
+            driver: "local".to_string(),
+            mountpoint: format!("/var/lib/docker/volumes/{name}/_data"),
+            created_at: Some(created.parse().unwrap()),
+            size,
+            in_use,
+            ..Default::default()
+        };
+        let backend = Arc::new(fixture().with_volumes(vec![
+            volume("pgdata", "2025-01-10T08:00:00Z", Some(2_000_000_000), true),
+            volume("ci_cache", "2024-11-02T08:00:00Z", Some(300_000_000), false),
+            volume("ci_build", "2025-06-30T08:00:00Z", None, false),
+            Volume {
+                labels: [("docker-cleanup.protect".to_string(), "true".to_string())].into(),
+                ..volume("keepme", "2024-01-01T00:00:00Z", Some(1_000), false)
+            },
+        ]));
+
+        let args = [
+            "ls",
+            "--kind",
+            "volume",
+            "--unused",
+            "--name-contains",
+            "ci_",
+        ];
+        let volumes = selected::<output::VolumeRecord>(backend.clone(), &args).unwrap();
+        let names = volumes.iter().map(|v| v.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["ci_cache", "ci_build"]);
+        let summary = output::VolumeRecord::summarise(&volumes);
+        assert_eq!((summary.size, summary.unknown_size), (300_000_000, 1));
+
+        let args = ["ls", "--kind", "volume", "--older-than", "1d", "--sort"];
+        let volumes = selected::<output::VolumeRecord>(backend.clone(), &args).unwrap();
+        assert_eq!(volumes[0].name, "pgdata");
+        assert_eq!(volumes.last().unwrap().name, "ci_build");
+
+        let args = ["rm", "--kind", "volume", "--keep-latest", "1"];
+        let error = selected::<output::VolumeRecord>(backend.clone(), &args).unwrap_err();
+        assert!(matches!(error, Error::InvalidQuery(_)));
+
+        let volumes =
+            selected::<output::VolumeRecord>(backend.clone(), &["rm", "--kind", "volume"]).unwrap();
+        let protection = Protection {
+            labels: vec![protect::PROTECT_LABEL.to_string()],
+            ..Default::default()
+        };
+        let removals = remove_records(backend.as_ref(), &volumes, &protection);
+        assert_eq!(backend.removed(), ["ci_cache", "ci_build"]);
+        let summary = output::RemovalSummary::of(&removals);
+        assert_eq!(
+            (summary.removed, summary.failed, summary.protected),
+            (2, 1, 1)
+        );
+        let in_use = removals.iter().find(|r| r.item.name == "pgdata").unwrap();
+        assert!(in_use.error.as_ref().unwrap().contains("in use"));
+    }
+
+    #[test]
+    fn build_cache_is_pruned_by_last_use() {
+        let now = jiff::Timestamp::now();
+        let days_ago = |days: i64| now - jiff::SignedDuration::from_hours(24 * days);
+        let record = |id: &str, last_used: Option<i64>, size: u64| BuildCache {
+            id: id.to_string(),
+            cache_type: "regular".to_string(),
+            size,
+            created_at: days_ago(30),
+            last_used_at: last_used.map(days_ago),
+            description: format!("[builder] RUN step {id}"),
+            ..Default::default()
+        };
+        let backend = Arc::new(fixture().with_build_cache(vec![
+            record("recent", Some(1), 100),
+            record("stale", Some(10), 200),
+            BuildCache {
+                shared: true,
+                ..record("never", None, 400)
+            },
+            BuildCache {
+                in_use: true,
+                ..record("busy", Some(20), 800)
+            },
+        ]));
+
+        let ids = |args: &[&str]| {
+            selected::<output::BuildCacheRecord>(backend.clone(), args)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(&["ls", "--kind", "build-cache", "--unused-for", "7d"]),
+            ["stale", "never", "busy"]
+        );
+        assert_eq!(
+            ids(&[
+                "ls",
+                "--kind",
+                "build-cache",
+                "--unused-for",
+                "7d",
+                "--unused"
+            ]),
+            ["stale", "never"]
+        );
+        assert_eq!(
+            ids(&["ls", "--kind", "build-cache", "--name-contains", "step r"]),
+            ["recent"]
+        );
+
+        let args = ["ls", "--kind", "build-cache", "--unused-for", "7d"];
+        let records = selected::<output::BuildCacheRecord>(backend.clone(), &args).unwrap();
+        let summary = output::BuildCacheRecord::summarise(&records);
+        assert_eq!((summary.size, summary.reclaimable), (1400, 1000));
+
+        let args = cli(&["ls", "--unused-for", "7d"]);
+        let error = select_images(backend.clone(), args.command.filter().unwrap()).unwrap_err();
+        assert!(matches!(error, Error::InvalidQuery(_)));
+
+        let removals = remove_records(backend.as_ref(), &records, &Protection::default());
+        assert_eq!(backend.removed(), ["stale", "never"]);
+        assert!(removals[2].error.as_ref().unwrap().contains("in use"));
+    }
+
+    #[test]
+    fn unused_networks_are_removed() {
+        let network = |id: &str, name: &str, created: &str| Network {
+            id: id.to_string(),
+            name: name.to_string(),
+            driver: "bridge".to_string(),
+            scope: "local".to_string(),
+            created_at: created.parse().unwrap(),
+            ..Default::default()
+        };
+        let backend = Arc::new(fixture().with_networks(vec![
+            network("b1", "bridge", "2024-01-01T00:00:00Z"),
+            network("a1", "app_default", "2025-07-20T09:00:00Z"),
+            network("c1", "ci_default", "2025-03-01T00:00:00Z"),
+            network("c2", "ci_test", "2025-07-01T00:00:00Z"),
+        ]));
+
+        let names = |args: &[&str]| selected_names::<output::NetworkRecord>(backend.clone(), args);
+        assert_eq!(
+            names(&["ls", "--kind", "network"]),
+            ["app_default", "ci_default", "ci_test"]
+        );
+        assert_eq!(
+            names(&["ls", "--kind", "network", "--unused"]),
+            ["ci_default", "ci_test"]
+        );
+        assert_eq!(
+            names(&[
+                "ls",
+                "--kind",
+                "network",
+                "--name-matches",
+                "^ci_",
+                "--created-before",
+                "2025-06-01T00:00:00Z"
+            ]),
+            ["ci_default"]
+        );
+
+        let args = ["rm", "--kind", "network"];
+        let networks = selected::<output::NetworkRecord>(backend.clone(), &args).unwrap();
+        let removals = remove_records(backend.as_ref(), &networks, &Protection::default());
+        assert_eq!(backend.removed(), ["c1", "c2"]);
+        assert!(
+            removals[0]
+                .error
+                .as_ref()
+                .unwrap()
+                .contains("active endpoints")
+        );
+
+        // A backend giving too few results fails what's left over rather than panicking
+        let removals = remove_batches(
+            &networks,
+            &Protection::default(),
+            1,
+            |_| vec![Ok(())],
+            &mut |_| {},
+        );
+        assert!(removals[0].removed);
+        assert!(removals[1].error.as_ref().unwrap().contains("no result"));
+    }
+
+    #[test]
+    fn stopped_containers_are_removed_before_images() {
+        let inspect = r#"[
+            {"Id": "3f4e0c1d2b6a", "State": {"Status": "exited", "ExitCode": 0, "FinishedAt": "2025-07-20T10:00:00.123456789Z"}, "SizeRw": 1000},
+            {"Id": "5d6e7f8a9b0c", "State": {"Status": "running", "ExitCode": 0, "FinishedAt": "0001-01-01T00:00:00Z"}, "SizeRw": 20}
+        ]"#;
+        let details = serde_json::from_str::<Vec<docker::ContainerInspect>>(inspect)
+            .unwrap()
+            .into_iter()
+            .map(ContainerDetails::from)
+            .collect::<Vec<_>>();
+        assert_eq!(details[0].exit_code, Some(0));
+        assert_eq!(details[0].size, Some(1000));
+        assert_eq!(details[1].exit_code, None);
+        assert_eq!(details[1].finished_at, None);
+
+        let container = |id: &str, name: &str, image: &str, state: &str| Container {
+            id: id.to_string(),
+            name: name.to_string(),
+            image: image.to_string(),
+            image_id: None,
+            state: state.to_string(),
+            status: String::new(),
+            created_at: "2025-07-21T00:00:00Z".parse().unwrap(),
+            networks: vec![],
+        };
+        let fixture = fixture().with_containers(vec![
+            container("5d6e7f8a9b0c", "busy_euler", "work/app:1", "running"),
+            container("7a8b9c0d1e2f", "sad_hopper", "ubuntu:18.04", "exited"),
+        ]);
+        let backend = Arc::new(fixture.with_details(vec![
+            details[0].clone(),
+            details[1].clone(),
+            ContainerDetails {
+                id: "7a8b9c0d1e2f".to_string(),
+                exit_code: Some(137),
+                finished_at: Some("2025-07-25T00:00:00Z".parse().unwrap()),
+                size: Some(5_000_000),
+            },
+        ]));
+
+        let names =
+            |args: &[&str]| selected_names::<output::ContainerRecord>(backend.clone(), args);
+        assert_eq!(
+            names(&["ls", "--kind", "container"]),
+            ["happy_turing", "sad_hopper"]
+        );
+        assert_eq!(
+            names(&["ls", "--kind", "container", "--exit-code", "137"]),
+            ["sad_hopper"]
+        );
+        assert_eq!(
+            names(&[
+                "ls",
+                "--kind",
+                "container",
+                "--exited-before",
+                "2025-07-22T00:00:00Z"
+            ]),
+            ["happy_turing"]
+        );
+        // docker.io/library/ubuntu is the same repository as ubuntu
+        assert_eq!(
+            names(&["ls", "--kind", "container", "--image", "ubuntu"]),
+            ["happy_turing", "sad_hopper"]
+        );
+        assert_eq!(
+            names(&["ls", "--kind", "container", "--image", "ubuntu:18.04"]),
+            ["sad_hopper"]
+        );
+        assert_eq!(
+            names(&["ls", "--kind", "container", "--larger-than", "1MB"]),
+            ["sad_hopper"]
+        );
+        let args = ["size", "--kind", "container"];
+        let containers = selected::<output::ContainerRecord>(backend.clone(), &args).unwrap();
+        assert_eq!(
+            output::ContainerRecord::summarise(&containers).size,
+            5_001_000
+        );
+
+        let args = cli(&["ls", "--exit-code", "1"]);
+        let error = select_images(backend.clone(), args.command.filter().unwrap()).unwrap_err();
+        assert!(matches!(error, Error::InvalidQuery(_)));
+
+        // Containers go first, after which the images they used count as unused
+        let args = cli(&[
+            "rm",
+            "--kind",
+            "image,container",
+            "--unused",
+            "--exit-code",
+            "0",
+        ]);
+        let filter = args.command.filter().unwrap();
+        assert_eq!(filter.kinds(), [Kind::Container, Kind::Image]);
+        let containers = select::<output::ContainerRecord>(backend.clone(), filter).unwrap();
+        remove_records(backend.as_ref(), &containers, &Protection::default());
+        assert_eq!(backend.removed(), ["3f4e0c1d2b6a"]);
+        let images = select_images(backend.clone(), filter).unwrap();
+        assert!(
+            images
+                .iter()
+                .any(|i| i.name == "docker.io/library/ubuntu:24.10")
+        );
+        assert!(images.iter().all(|i| i.name != "ubuntu:18.04"));
+
+        let removal = backend.remove_container("5d6e7f8a9b0c").unwrap_err();
+        assert!(removal.to_string().contains("container is running"));
+    }
+
+    #[test]
+    fn images_are_filtered_by_registry() {
+        let backend = fixture();
+        let local = image(
+            "1a2b3c4d5e6f",
+            "localhost:5000/app",
+            "1.2",
+            10_000_000,
+            "2025-07-10T00:00:00Z",
+        );
+        let backend = Arc::new(Fixture::new(
+            [backend.images().unwrap(), vec![local]].concat(),
+            backend.containers().unwrap(),
+        ));
+
+        let images = |args: &[&str]| {
+            let args = cli(args);
+            select_images(backend.clone(), args.command.filter().unwrap()).unwrap()
+        };
+        let local = images(&["ls", "--registry", "localhost:5000"]);
+        assert_eq!(local.len(), 1);
+        assert_eq!(local[0].namespace.as_deref(), Some(""));
+        assert_eq!(local[0].repo.as_deref(), Some("app"));
+        assert_eq!(local[0].tag.as_deref(), Some("1.2"));
+
+        // docker's short names are on Docker Hub
+        let hub = images(&["ls", "--registry", "index.docker.io"]);
+        let names = hub.iter().map(|i| i.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "docker.io/library/ubuntu:24.10",
+                "ubuntu:18.04",
+                "work/app:1"
+            ]
+        );
+        assert_eq!(hub[1].namespace.as_deref(), Some("library"));
+        assert_eq!(hub[2].namespace.as_deref(), Some("work"));
+
+        let args = ["ls", "--kind", "volume", "--registry", "ghcr.io"];
+        let error = selected::<output::VolumeRecord>(backend.clone(), &args).unwrap_err();
+        assert!(matches!(error, Error::InvalidQuery(_)));
+    }
+
+    #[test]
+    fn images_with_several_names() {
+        // docker prints a row per name
+        let rows = [
+            r#"{"CreatedAt":"2025-07-02 10:00:00 +0100 BST","Digest":"\u003cnone\u003e","ID":"9a8b7c6d5e4f","Repository":"work/app","Size":"1.2GB","Tag":"1.2"}"#,
+            r#"{"CreatedAt":"2025-07-02 10:00:00 +0100 BST","Digest":"\u003cnone\u003e","ID":"9a8b7c6d5e4f","Repository":"work/app","Size":"1.2GB","Tag":"pr-7"}"#,
+        ];
+        let images = images::merge_names(
+            rows.iter()
+                .map(|row| serde_json::from_str::<docker::Image>(row).unwrap().into())
+                .collect(),
+        );
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].references, ["work/app:1.2", "work/app:pr-7"]);
+
+        let app = Image {
+            references: vec![
+                "work/app:1.2".to_string(),
+                "work/app:latest".to_string(),
+                "work/app:pr-7".to_string(),
+            ],
+            ..image(
+                "1a2b3c4d5e6f",
+                "work/app",
+                "1.2",
+                1_000_000,
+                "2025-07-10T00:00:00Z",
+            )
+        };
+        let backend = Arc::new(Fixture::new(vec![app], vec![]));
+        let select = |args: &[&str]| {
+            let args = cli(args);
+            select_images(backend.clone(), args.command.filter().unwrap()).unwrap()
+        };
+
+        let listed = select(&["ls", "--name-contains", "pr-"]);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "work/app:1.2");
+        assert_eq!(listed[0].names().len(), 3);
+
+        // Untagging leaves the image and its other names
+        let untag = select(&["rm", "--untag", "--name-contains", "pr-"]);
+        assert_eq!(untag[0].references(), ["work/app:pr-7"]);
+        let removals = remove_images(backend.as_ref(), &untag, &Protection::default(), 1, |_| {});
+        assert!(removals[0].removed);
+        assert_eq!(output::RemovalSummary::of(&removals).freed, 0);
+        assert_eq!(backend.removed(), ["work/app:pr-7"]);
+        assert_eq!(
+            backend.images().unwrap()[0].references,
+            ["work/app:1.2", "work/app:latest"]
+        );
+
+        // Whereas rm removes every name so the image goes
+        let images = select(&["rm", "--name", "work/app:latest"]);
+        assert_eq!(images[0].references(), ["work/app:1.2", "work/app:latest"]);
+        remove_images(backend.as_ref(), &images, &Protection::default(), 1, |_| {});
+        assert!(backend.images().unwrap().is_empty());
+    }
+
+    #[test]
+    fn untag_reports_which_names_delete_images() {
+        let named = |hash: &str, names: &[&str], size| Image {
+            references: names.iter().map(|n| n.to_string()).collect(),
+            ..image(hash, "work/app", "1.2", size, "2025-07-10T00:00:00Z")
+        };
+        let backend = Arc::new(Fixture::new(
+            vec![
+                named(
+                    "1a2b3c4d5e6f",
+                    &["work/app:1.2", "work/app:pr-7", "work/app:pr-8"],
+                    1_000_000,
+                ),
+                named(
+                    "6f5e4d3c2b1a",
+                    &["work/app:pr-9", "work/app:pr-10"],
+                    2_000_000,
+                ),
+            ],
+            vec![],
+        ));
+        let args = cli(&["untag", "--name-contains", "pr-"]);
+        let filter = args.command.filter().unwrap();
+        let (query_str, query_args) = build_query(filter).unwrap();
+        let images = find_images(backend.clone(), &query_str, query_args, false, true).unwrap();
+        let names = plan_untags(&images.iter().flat_map(image_names).collect::<Vec<_>>());
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[0].tag.as_deref(), Some("pr-7"));
+
+        let removals = untag_names(
+            backend.as_ref(),
+            &images,
+            &names,
+            &Protection::default(),
+            2,
+            |_| {},
+        );
+        let deleted = removals
+            .iter()
+            .filter(|r| r.removed && r.item.deletes_image)
+            .map(|r| r.item.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(deleted, ["work/app:pr-10"]);
+        assert!(removals.iter().all(|r| r.removed));
+        assert_eq!(output::RemovalSummary::of(&removals).freed, 2_000_000);
+
+        let images = backend.images().unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].references, ["work/app:1.2"]);
+
+        // The last name of an image others are built on is untagged without deleting it
+        let child = Image {
+            parent: Some("b1".to_string()),
+            ..image("c1", "child", "1", 100, "2025-07-11T00:00:00Z")
+        };
+        let backend = Arc::new(Fixture::new(
+            vec![
+                image("b1", "base", "pr-1", 500, "2025-07-10T00:00:00Z"),
+                child,
+            ],
+            vec![],
+        ));
+        let args = cli(&["untag", "--name", "base:pr-1"]);
+        let (query_str, query_args) = build_query(args.command.filter().unwrap()).unwrap();
+        let images = find_images(backend.clone(), &query_str, query_args, false, true).unwrap();
+        let names = plan_untags(&images.iter().flat_map(image_names).collect::<Vec<_>>());
+        assert!(names[0].deletes_image);
+        let removals = untag_names(
+            backend.as_ref(),
+            &images,
+            &names,
+            &Protection::default(),
+            1,
+            |_| {},
+        );
+        assert!(removals[0].removed && !removals[0].item.deletes_image);
+        assert_eq!(output::RemovalSummary::of(&removals).freed, 0);
+        assert_eq!(backend.images().unwrap().len(), 2);
+
+        // untag only works on names
+        let args = cli(&["untag", "--kind", "volume"]);
+        assert!(run_untag(backend.clone(), args.command.filter().unwrap()).is_err());
+    }
 }
@@ -0,0 +1,133 @@
+use crate::args::FilterOptions;
+use crate::backend::Backend;
+use crate::error::Result;
+use crate::output::{NetworkRecord, NetworkSummary, Row};
+use crate::{Container, QueryArgs, Record, docker, podman};
+use jiff::Timestamp;
+use std::collections::BTreeMap;
+use trustfall::FieldValue;
+
+/// Networks the runtimes create themselves and won't let you remove.
+pub const PREDEFINED_NETWORKS: &[&str] = &["bridge", "host", "none", "podman"];
+
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct Network {
+    pub id: String,
+    pub name: String,
+    pub driver: String,
+    /// `local` or `swarm`, podman networks are always local
+    pub scope: String,
+    pub created_at: Timestamp,
+    pub labels: BTreeMap<String, String>,
+    /// Whether it's cut off from outside networks
+    pub internal: bool,
+}
+
+impl Network {
+    pub fn is_predefined(&self) -> bool {
+        PREDEFINED_NETWORKS.contains(&self.name.as_str())
+    }
+
+    /// Whether the container is attached to this network, which containers list by name.
+    pub fn is_attached(&self, container: &Container) -> bool {
+        container
+            .networks
+            .iter()
+            .any(|n| *n == self.name || *n == self.id)
+    }
+}
+
+impl From<docker::Network> for Network {
+    fn from(n: docker::Network) -> Self {
+        Self {
+            id: n.id,
+            name: n.name,
+            driver: n.driver,
+            scope: n.scope,
+            created_at: n.created.unwrap_or_default(),
+            labels: n.labels.unwrap_or_default(),
+            internal: n.internal,
+        }
+    }
+}
+
+impl From<podman::Network> for Network {
+    fn from(n: podman::Network) -> Self {
+        Self {
+            id: n.id,
+            name: n.name,
+            driver: n.driver,
+            scope: "local".to_string(),
+            created_at: n.created.unwrap_or_default(),
+            labels: n.labels.unwrap_or_default(),
+            internal: n.internal,
+        }
+    }
+}
+
+/// Only the networks the runtime didn't make itself are queried, since those can't be removed.
+impl Record for NetworkRecord {
+    type Summary = NetworkSummary;
+
+    fn query(filter: &FilterOptions) -> Result<(String, QueryArgs)> {
+        crate::check_filters(filter)?;
+
+        let mut query_str = "{Network{".to_string();
+        query_str.push_str(&crate::created_edges(filter));
+
+        let mut query_args = QueryArgs::new();
+        let id_filter = crate::id_filters(filter, &mut query_args);
+        let name_filter = crate::name_filters(filter, &mut query_args);
+
+        query_str.push_str(r#"predefined @filter(op: "=", value: ["$predefined"])"#);
+        query_str.push('\n');
+        query_args.insert("predefined".into(), false.into());
+
+        if filter.unused {
+            query_str.push_str(
+                r#"containers @fold @transform(op: "count") @filter(op: "=", value: ["$no_containers"])"#,
+            );
+            query_str.push('\n');
+            query_args.insert("no_containers".into(), FieldValue::Int64(0));
+        }
+
+        query_str.push_str(&crate::label_filters(filter, &mut query_args));
+
+        query_str.push_str(&format!(
+            "id @output\n{id_filter}name @output\n{name_filter}driver @output\nscope @output\ncreated @output\ninternal @output\nlabels @output\n"
+        ));
+
+        query_str.push_str("}}");
+        Ok((query_str, query_args))
+    }
+
+    fn from_row(row: Row) -> Self {
+        let as_string = |v: &FieldValue| v.as_str().unwrap().to_string();
+        Self {
+            id: as_string(&row["id"]),
+            name: as_string(&row["name"]),
+            driver: as_string(&row["driver"]),
+            scope: as_string(&row["scope"]),
+            created: as_string(&row["created"]),
+            internal: row["internal"].as_bool().unwrap(),
+            labels: row["labels"]
+                .as_vec_with(|v| v.as_str().map(|s| s.to_string()))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Networks have no size, so oldest first.
+    fn sort(networks: &mut [Self]) {
+        networks.sort_by(|a, b| a.created.cmp(&b.created));
+    }
+
+    fn summarise(networks: &[Self]) -> NetworkSummary {
+        NetworkSummary {
+            networks: networks.len(),
+        }
+    }
+
+    fn remove(backend: &dyn Backend, ids: &[&str]) -> Vec<Result<()>> {
+        backend.remove_networks(ids)
+    }
+}
@@ -0,0 +1,758 @@
+//! Printing results either for people (tables) or for scripts (JSON, NDJSON and CSV).
+use crate::args::OutputFormat;
+use crate::layers::LayerUsage;
+use crate::policy::Action;
+use human_size::{SpecificSize, multiples::*};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use trustfall::{FieldValue, TransparentValue};
+
+pub type Row = BTreeMap<Arc<str>, FieldValue>;
+
+/// Everything we resolve for an image the filters selected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageRecord {
+    pub id: String,
+    pub name: String,
+    pub registry: Option<String>,
+    pub namespace: Option<String>,
+    pub repo: Option<String>,
+    pub tag: Option<String>,
+    pub size: u64,
+    pub created: String,
+    pub dangling: bool,
+    pub labels: Vec<String>,
+    /// ID of the image this was built on, if that's still around
+    pub parent: Option<String>,
+    /// Every name of the image, `name` is the first
+    pub references: Vec<String>,
+    /// With `rm --untag`, the names the filters matched, which are all that's removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub untag: Option<Vec<String>>,
+}
+
+impl ImageRecord {
+    fn names_to_remove(&self) -> &[String] {
+        self.untag.as_deref().unwrap_or(&self.references)
+    }
+}
+
+/// Anything `rm` can remove, so confirming, protecting and removing work the same way for every
+/// kind of thing.
+pub trait Removable: Clone + Serialize + Send + Sync {
+    /// What one of them is called, e.g. `image`
+    const KIND: &'static str;
+
+    fn id(&self) -> &str;
+    /// How it's shown to people
+    fn name(&self) -> &str;
+    /// Every name it goes by, listed and checked for protection
+    fn names(&self) -> Vec<&str> {
+        vec![self.name()]
+    }
+    /// What to pass to the runtime to remove it, it's only removed if all of them are
+    fn references(&self) -> Vec<&str>;
+    /// Bytes on disk, if the runtime says
+    fn size(&self) -> Option<u64>;
+    /// Whether removing it frees its size
+    fn frees_space(&self) -> bool {
+        true
+    }
+    /// Each label as `key=value`
+    fn labels(&self) -> &[String];
+    /// What's printed when it's been removed, or tried to be
+    fn removal_message(&self, _removed: bool) -> String {
+        format!("Removing: {}", self.names().join(", "))
+    }
+}
+
+impl Removable for ImageRecord {
+    const KIND: &'static str = "image";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Just the names being untagged with `rm --untag`.
+    fn names(&self) -> Vec<&str> {
+        match self.names_to_remove() {
+            [] => vec![&self.name],
+            names => names.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Untagging an image that keeps other names frees nothing.
+    fn frees_space(&self) -> bool {
+        self.names_to_remove().len() == self.references.len()
+    }
+
+    /// Every name, removing the last deletes the image. Untagged images only have their ID.
+    fn references(&self) -> Vec<&str> {
+        match self.names_to_remove() {
+            [] => vec![&self.id],
+            names => names.iter().map(String::as_str).collect(),
+        }
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.size)
+    }
+
+    fn labels(&self) -> &[String] {
+        &self.labels
+    }
+}
+
+/// What `size` or a dry run reports, worked out before removing anything so it can also tell
+/// what the removals freed.
+pub trait Summary: fmt::Display + Serialize {
+    /// Bytes freed by removing everything summarised
+    fn reclaimable(&self) -> u64;
+    /// Bytes freed by removing just these, which were summarised. Unless things share data
+    /// that's their sizes added up.
+    fn freed<T: Removable>(&self, removed: &[&T]) -> u64 {
+        removed
+            .iter()
+            .filter(|i| i.frees_space())
+            .filter_map(|i| i.size())
+            .sum()
+    }
+}
+
+/// IDs of the removed items that freed their space.
+fn freeing_ids<'a, T: Removable>(removed: &[&'a T]) -> Vec<&'a str> {
+    removed
+        .iter()
+        .filter(|i| i.frees_space())
+        .map(|i| i.id())
+        .collect()
+}
+
+/// A name of an image the filters selected, `untag` removes them one at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReferenceRecord {
+    pub name: String,
+    /// ID of the image it names
+    pub image: String,
+    pub registry: Option<String>,
+    pub namespace: Option<String>,
+    pub repo: Option<String>,
+    pub tag: Option<String>,
+    /// The image's size
+    pub size: u64,
+    /// The image's labels
+    pub labels: Vec<String>,
+    /// Whether untagging it is expected to delete the image, because it's the last name the
+    /// image has. Once untagged, whether the runtime said it deleted the image.
+    pub deletes_image: bool,
+    /// How many names the image has
+    #[serde(skip)]
+    pub image_names: usize,
+}
+
+impl Removable for ReferenceRecord {
+    const KIND: &'static str = "image name";
+
+    fn id(&self) -> &str {
+        &self.image
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn references(&self) -> Vec<&str> {
+        vec![&self.name]
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.size)
+    }
+
+    /// Only the untag that deletes the image frees anything.
+    fn frees_space(&self) -> bool {
+        self.deletes_image
+    }
+
+    fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    fn removal_message(&self, removed: bool) -> String {
+        if removed && self.deletes_image {
+            format!(
+                "Untagging: {}, deleted image {}",
+                self.name,
+                short_id(&self.image)
+            )
+        } else {
+            format!("Untagging: {}", self.name)
+        }
+    }
+}
+
+/// What `untag` would do, only images losing every name free any space.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UntagSummary {
+    pub names: usize,
+    /// Images left without a name, which are deleted
+    pub images_deleted: usize,
+    pub reclaimable: u64,
+    #[serde(skip)]
+    pub usage: LayerUsage,
+}
+
+impl Summary for UntagSummary {
+    fn reclaimable(&self) -> u64 {
+        self.reclaimable
+    }
+
+    fn freed<T: Removable>(&self, removed: &[&T]) -> u64 {
+        self.usage.reclaimable(&freeing_ids(removed))
+    }
+}
+
+impl fmt::Display for UntagSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} names, untagging them would delete {} images and free {}",
+            self.names,
+            self.images_deleted,
+            human_size(self.reclaimable)
+        )
+    }
+}
+
+/// Everything we resolve for a volume the filters selected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VolumeRecord {
+    pub name: String,
+    pub driver: String,
+    pub mountpoint: String,
+    pub created: Option<String>,
+    pub size: Option<u64>,
+    pub in_use: bool,
+    pub labels: Vec<String>,
+}
+
+/// Volumes only have a name, it's used wherever an ID would be.
+impl Removable for VolumeRecord {
+    const KIND: &'static str = "volume";
+
+    fn id(&self) -> &str {
+        &self.name
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn references(&self) -> Vec<&str> {
+        vec![&self.name]
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    fn labels(&self) -> &[String] {
+        &self.labels
+    }
+}
+
+/// Everything we resolve for a build cache record the filters selected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildCacheRecord {
+    pub id: String,
+    /// The description, or the ID for records without one
+    pub name: String,
+    #[serde(rename = "type")]
+    pub cache_type: String,
+    pub size: u64,
+    pub shared: bool,
+    pub in_use: bool,
+    pub created: String,
+    pub last_used: Option<String>,
+    pub usage_count: u64,
+}
+
+/// Records have no labels, so only the patterns can protect them.
+impl Removable for BuildCacheRecord {
+    const KIND: &'static str = "build cache record";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn references(&self) -> Vec<&str> {
+        vec![&self.id]
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.size)
+    }
+
+    fn labels(&self) -> &[String] {
+        &[]
+    }
+}
+
+/// Everything we resolve for a stopped container the filters selected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ContainerRecord {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub state: String,
+    pub created: String,
+    pub exit_code: Option<i64>,
+    pub finished: Option<String>,
+    /// The writable layer, the image's layers are left for removing the image
+    pub size: Option<u64>,
+}
+
+impl Removable for ContainerRecord {
+    const KIND: &'static str = "container";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn references(&self) -> Vec<&str> {
+        vec![&self.id]
+    }
+
+    fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    fn labels(&self) -> &[String] {
+        &[]
+    }
+}
+
+/// Everything we resolve for a network the filters selected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetworkRecord {
+    pub id: String,
+    pub name: String,
+    pub driver: String,
+    pub scope: String,
+    pub created: String,
+    pub internal: bool,
+    pub labels: Vec<String>,
+}
+
+/// Networks don't take up any disk space.
+impl Removable for NetworkRecord {
+    const KIND: &'static str = "network";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn references(&self) -> Vec<&str> {
+        vec![&self.id]
+    }
+
+    fn size(&self) -> Option<u64> {
+        None
+    }
+
+    fn labels(&self) -> &[String] {
+        &self.labels
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SizeSummary {
+    pub images: usize,
+    /// The images' sizes added up, counting shared layers once per image
+    pub size: u64,
+    /// What removing the images would actually free
+    pub reclaimable: u64,
+    #[serde(skip)]
+    pub usage: LayerUsage,
+}
+
+impl Summary for SizeSummary {
+    fn reclaimable(&self) -> u64 {
+        self.reclaimable
+    }
+
+    fn freed<T: Removable>(&self, removed: &[&T]) -> u64 {
+        self.usage.reclaimable(&freeing_ids(removed))
+    }
+}
+
+impl fmt::Display for SizeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} images totalling {}, removing them would free {}",
+            self.images,
+            human_size(self.size),
+            human_size(self.reclaimable)
+        )
+    }
+}
+
+/// Nothing is shared between volumes, so removing them frees their total size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VolumeSizeSummary {
+    pub volumes: usize,
+    /// The sizes that are known added up
+    pub size: u64,
+    /// How many volumes the runtime didn't give a size for
+    pub unknown_size: usize,
+}
+
+impl Summary for VolumeSizeSummary {
+    fn reclaimable(&self) -> u64 {
+        self.size
+    }
+}
+
+impl fmt::Display for VolumeSizeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} volumes totalling {}",
+            self.volumes,
+            human_size(self.size)
+        )?;
+        if self.unknown_size > 0 {
+            write!(f, ", {} of unknown size", self.unknown_size)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ContainerSizeSummary {
+    pub containers: usize,
+    /// Their writable layers added up
+    pub size: u64,
+}
+
+impl Summary for ContainerSizeSummary {
+    fn reclaimable(&self) -> u64 {
+        self.size
+    }
+}
+
+impl fmt::Display for ContainerSizeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} containers totalling {}",
+            self.containers,
+            human_size(self.size)
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildCacheSizeSummary {
+    pub records: usize,
+    pub size: u64,
+    /// Leaves out records whose data is shared with something else
+    pub reclaimable: u64,
+}
+
+impl Summary for BuildCacheSizeSummary {
+    fn reclaimable(&self) -> u64 {
+        self.reclaimable
+    }
+}
+
+impl fmt::Display for BuildCacheSizeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} build cache records totalling {}, removing them would free {}",
+            self.records,
+            human_size(self.size),
+            human_size(self.reclaimable)
+        )
+    }
+}
+
+/// There's no size to networks, so it's just how many.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetworkSummary {
+    pub networks: usize,
+}
+
+impl Summary for NetworkSummary {
+    fn reclaimable(&self) -> u64 {
+        0
+    }
+}
+
+impl fmt::Display for NetworkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} networks", self.networks)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Removal<T = ImageRecord> {
+    #[serde(flatten)]
+    pub item: T,
+    pub removed: bool,
+    /// Skipped without trying because it's protected
+    pub protected: bool,
+    pub error: Option<String>,
+}
+
+/// Totals for a batch of removals.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RemovalSummary {
+    pub removed: usize,
+    pub failed: usize,
+    pub protected: usize,
+    pub freed: u64,
+}
+
+impl RemovalSummary {
+    /// Bytes freed counts each removed item's full size, which is too much when they share
+    /// data.
+    pub fn of<T: Removable>(removals: &[Removal<T>]) -> Self {
+        let mut summary = Self::default();
+        for removal in removals {
+            if removal.protected {
+                summary.protected += 1;
+            } else if removal.removed {
+                summary.removed += 1;
+                if removal.item.frees_space() {
+                    summary.freed += removal.item.size().unwrap_or_default();
+                }
+            } else {
+                summary.failed += 1;
+            }
+        }
+        summary
+    }
+
+    /// Bytes freed is what the summary taken before removing says the removed items free.
+    pub fn with_summary<T: Removable>(removals: &[Removal<T>], planned: &impl Summary) -> Self {
+        let removed = removals
+            .iter()
+            .filter(|r| r.removed)
+            .map(|r| &r.item)
+            .collect::<Vec<_>>();
+        Self {
+            freed: planned.freed(&removed),
+            ..Self::of(removals)
+        }
+    }
+}
+
+impl fmt::Display for RemovalSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} removed, {} failed, {} protected, {} freed",
+            self.removed,
+            self.failed,
+            self.protected,
+            human_size(self.freed)
+        )
+    }
+}
+
+/// Formats a byte count as megabytes, or gigabytes once it's big enough.
+pub fn human_size(bytes: u64) -> String {
+    let human_size = SpecificSize::new(bytes as f64, Byte).unwrap();
+    if bytes > 1_000_000_000 {
+        let s: SpecificSize<Gigabyte> = human_size.into();
+        s.to_string()
+    } else {
+        let s: SpecificSize<Megabyte> = human_size.into();
+        s.to_string()
+    }
+}
+
+/// The first 12 characters of an image ID, like the runtimes show.
+pub fn short_id(id: &str) -> &str {
+    let id = id.trim_start_matches("sha256:");
+    &id[..id.len().min(12)]
+}
+
+/// Like [`human_size`] but for sizes the runtime might not know.
+pub fn size_or_unknown(bytes: Option<u64>) -> String {
+    bytes.map_or_else(|| "-".to_string(), human_size)
+}
+
+/// What a policy rule matched and, for `rm` rules, whether it was removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleRecord {
+    pub rule: String,
+    pub action: Action,
+    #[serde(flatten)]
+    pub image: ImageRecord,
+    pub removed: Option<bool>,
+    pub protected: bool,
+    pub error: Option<String>,
+}
+
+/// Prints a removal as it happens, JSON and CSV can only be printed once they're all done.
+pub fn print_removal<T: Removable>(removal: &Removal<T>, format: OutputFormat) {
+    match format {
+        OutputFormat::Table if removal.protected => {
+            println!("{}: skipped (protected)", removal.item.names().join(", "));
+        }
+        OutputFormat::Table => {
+            println!("{}", removal.item.removal_message(removal.removed));
+            if let Some(e) = &removal.error {
+                println!("{}", e);
+            }
+        }
+        OutputFormat::Ndjson => print_record(removal, format),
+        OutputFormat::Json | OutputFormat::Csv => {}
+    }
+}
+
+fn to_object<T: Serialize>(record: &T) -> Map<String, Value> {
+    match serde_json::to_value(record).expect("records always serialize") {
+        Value::Object(map) => map,
+        other => Map::from_iter([("value".to_string(), other)]),
+    }
+}
+
+fn row_to_object(row: &Row) -> Map<String, Value> {
+    row.iter()
+        .map(|(k, v)| {
+            let value = serde_json::to_value(TransparentValue::from(v.clone())).unwrap_or_default();
+            (k.to_string(), value)
+        })
+        .collect()
+}
+
+/// Plain text for a single cell, strings aren't quoted and lists are comma separated.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values.iter().map(display).collect::<Vec<_>>().join(","),
+        other => other.to_string(),
+    }
+}
+
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+pub fn print_rows(rows: &[Row], format: OutputFormat) {
+    let objects = rows.iter().map(row_to_object).collect::<Vec<_>>();
+    print_objects(&objects, format);
+}
+
+pub fn print_records<T: Serialize>(records: &[T], format: OutputFormat) {
+    let objects = records.iter().map(to_object).collect::<Vec<_>>();
+    print_objects(&objects, format);
+}
+
+/// Prints one record on its own, for NDJSON this can be used to stream results as they happen.
+pub fn print_record<T: Serialize>(record: &T, format: OutputFormat) {
+    print_records(std::slice::from_ref(record), format);
+}
+
+fn print_objects(objects: &[Map<String, Value>], format: OutputFormat) {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(objects).unwrap());
+        }
+        OutputFormat::Ndjson => {
+            for object in objects {
+                println!("{}", serde_json::to_string(object).unwrap());
+            }
+        }
+        OutputFormat::Csv => {
+            let Some(first) = objects.first() else {
+                return;
+            };
+            let header = first.keys().map(|k| csv_escape(k)).collect::<Vec<_>>();
+            println!("{}", header.join(","));
+            for object in objects {
+                let line = first
+                    .keys()
+                    .map(|k| csv_escape(&display(&object[k])))
+                    .collect::<Vec<_>>();
+                println!("{}", line.join(","));
+            }
+        }
+        OutputFormat::Table => print_table(objects),
+    }
+}
+
+fn print_table(objects: &[Map<String, Value>]) {
+    let Some(first) = objects.first() else {
+        return;
+    };
+    let columns = first.keys().cloned().collect::<Vec<_>>();
+    let cells = objects
+        .iter()
+        .map(|o| columns.iter().map(|c| display(&o[c])).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let widths = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            cells
+                .iter()
+                .map(|r| r[i].len())
+                .chain(std::iter::once(c.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    for line in std::iter::once(&columns).chain(cells.iter()) {
+        let line = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_cells_are_escaped() {
+        assert_eq!(csv_escape("ubuntu:24.10"), "ubuntu:24.10");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        let labels = serde_json::json!(["a=1", "b=2"]);
+        assert_eq!(csv_escape(&display(&labels)), "\"a=1,b=2\"");
+    }
+}
@@ -1,5 +1,6 @@
 use jiff::Timestamp;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Ord, PartialOrd, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -10,6 +11,52 @@ pub struct Image {
     pub history: Vec<String>,
     #[serde(default)]
     pub names: Vec<String>,
+    #[serde(default)]
+    pub digest: String,
+    #[serde(default)]
+    pub repo_digests: Vec<String>,
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    pub shared_size: Option<usize>,
     pub created: usize,
     pub created_at: Timestamp,
 }
+
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Ord, PartialOrd, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Container {
+    pub id: String,
+    pub image: String,
+    #[serde(rename = "ImageID")]
+    pub image_id: String,
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub state: String,
+    #[serde(default)]
+    pub status: String,
+    pub created: i64,
+    #[serde(default)]
+    pub networks: Option<Vec<String>>,
+}
+
+/// An entry of `podman network ls --format json`.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+pub struct Network {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub driver: String,
+    #[serde(default)]
+    pub created: Option<Timestamp>,
+    #[serde(default)]
+    pub internal: bool,
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
+}
+
+/// An entry of `podman image history --format json`.
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Ord, PartialOrd, Deserialize)]
+pub struct History {
+    pub size: i64,
+}
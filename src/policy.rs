@@ -0,0 +1,140 @@
+//! Cleanup policies, a TOML file of rules which are each turned into the same filters the
+//! command line takes and run in order.
+//!
+//! ```toml
+//! [protect]
+//! patterns = ["ubuntu:*"]
+//!
+//! [[rule]]
+//! name = "old-ci-images"
+//! name_matches = "^ci/"
+//! older_than = "14d"
+//! keep_latest = 3
+//! protected_labels = ["keep=true"]
+//! action = "rm"
+//! ```
+use crate::args::{FilterOptions, parse_human_size};
+use crate::error::{self, Error};
+use crate::protect::Protection;
+use jiff::Span;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Protected images on top of the usual protection file
+    #[serde(default)]
+    pub protect: Protection,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Just report what matches
+    #[default]
+    Ls,
+    /// Remove what matches
+    Rm,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ls => write!(f, "ls"),
+            Self::Rm => write!(f, "rm"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub action: Action,
+    /// Regex the image name has to match
+    pub name_matches: Option<String>,
+    pub name_contains: Option<String>,
+    /// Exact image name
+    pub image: Option<String>,
+    pub older_than: Option<Span>,
+    pub newer_than: Option<Span>,
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub larger_than: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub smaller_than: Option<usize>,
+    pub keep_latest: Option<usize>,
+    #[serde(default)]
+    pub unused: bool,
+    #[serde(default)]
+    pub include_untagged: bool,
+    #[serde(default)]
+    pub only_untagged: bool,
+    /// Only match images with all of these `key=value` labels
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Never match images with any of these `key=value` labels
+    #[serde(default)]
+    pub protected_labels: Vec<String>,
+}
+
+/// Sizes can be given in bytes or as a human readable string like `"1GB"`.
+fn deserialize_size<'de, D>(d: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(usize),
+        Human(String),
+    }
+
+    match Size::deserialize(d)? {
+        Size::Bytes(b) => Ok(Some(b)),
+        Size::Human(s) => parse_human_size(&s)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> error::Result<Self> {
+        let config_error = |message: String| Error::Config {
+            path: path.to_path_buf(),
+            message,
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| config_error(e.to_string()))?;
+        Self::parse(&text).map_err(|e| config_error(e.to_string()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+impl Rule {
+    /// The command line filters equivalent to this rule.
+    pub fn filter(&self) -> FilterOptions {
+        FilterOptions {
+            name_matches: self.name_matches.clone(),
+            name_contains: self.name_contains.clone(),
+            name: self.image.clone(),
+            older_than: self.older_than,
+            newer_than: self.newer_than,
+            larger_than: self.larger_than,
+            smaller_than: self.smaller_than,
+            keep_latest: self.keep_latest,
+            unused: self.unused,
+            include_untagged: self.include_untagged,
+            only_untagged: self.only_untagged,
+            labels: self.labels.clone(),
+            exclude_labels: self.protected_labels.clone(),
+            ..Default::default()
+        }
+    }
+}
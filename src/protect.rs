@@ -0,0 +1,150 @@
+//! Images that `rm` must never delete, however broad the filters. Protection is checked after
+//! the query runs so no query can get around it, only `--force-protected` can.
+//!
+//! The list comes from a TOML file, by default `~/.config/docker-cleanup/protect.toml`:
+//!
+//! ```toml
+//! patterns = ["ubuntu:*", "registry.example.com/base/*"]
+//! labels = ["tier=base"]
+//! ```
+//!
+//! Images labelled `docker-cleanup.protect=true` are always protected.
+use crate::args::ProtectOptions;
+use crate::error::{Error, Result};
+use crate::output::Removable;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+pub const PROTECT_LABEL: &str = "docker-cleanup.protect=true";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Protection {
+    /// Glob patterns matched against the image name or ID, `*` matches anything
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// `key=value` labels that protect an image
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl Protection {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config_error = |message: String| Error::Config {
+            path: path.to_path_buf(),
+            message,
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| config_error(e.to_string()))?;
+        toml::from_str(&text).map_err(|e| config_error(e.to_string()))
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("docker-cleanup").join("protect.toml"))
+    }
+
+    /// Combines the protection file, the default one if none was given, with any patterns
+    /// from the command line. With `--force-protected` nothing is protected.
+    pub fn from_options(opts: &ProtectOptions) -> Result<Self> {
+        if opts.force_protected {
+            return Ok(Self::default());
+        }
+        let mut protection = match &opts.protect_file {
+            Some(path) => Self::load(path)?,
+            None => match Self::default_path().filter(|p| p.exists()) {
+                Some(path) => Self::load(&path)?,
+                None => Self::default(),
+            },
+        };
+        protection.patterns.extend(opts.protect.iter().cloned());
+        protection.labels.push(PROTECT_LABEL.to_string());
+        Ok(protection)
+    }
+
+    pub fn is_protected(&self, item: &impl Removable) -> bool {
+        self.labels.iter().any(|l| item.labels().contains(l))
+            || self
+                .patterns
+                .iter()
+                .any(|p| item.names().iter().any(|n| glob_match(p, n)) || glob_match(p, item.id()))
+    }
+}
+
+/// Matches `*` against any run of characters and `?` against any single one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Where to resume from if the most recent `*` needs to match more characters
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::ImageRecord;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("ubuntu:*", "ubuntu:24.10"));
+        assert!(glob_match(
+            "*/base/*",
+            "registry.example.com/base/rust:1.88"
+        ));
+        assert!(glob_match("app:1.?", "app:1.2"));
+        assert!(!glob_match("app:1.?", "app:1.23"));
+        assert!(!glob_match("ubuntu:*", "work/ubuntu:24.10"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn protected_by_label_name_or_id() {
+        let image = ImageRecord {
+            id: "71eaf13299f4c0bd2e4b7b8d9e1a5c3f6d0e2b4a8c7f9e1d3b5a7c9e0f2d4b6a".to_string(),
+            name: "ubuntu:18.04".to_string(),
+            registry: None,
+            namespace: None,
+            repo: Some("ubuntu".to_string()),
+            tag: Some("18.04".to_string()),
+            size: 63_100_000,
+            created: String::new(),
+            dangling: false,
+            labels: vec![PROTECT_LABEL.to_string()],
+            parent: None,
+            references: vec!["ubuntu:18.04".to_string()],
+            untag: None,
+        };
+        let protection = |patterns: &[&str], labels: &[&str]| Protection {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+        };
+        assert!(protection(&[], &[PROTECT_LABEL]).is_protected(&image));
+        assert!(!protection(&[], &["team=work"]).is_protected(&image));
+        assert!(protection(&["ubuntu:*"], &[]).is_protected(&image));
+        assert!(protection(&["71eaf13299f4*"], &[]).is_protected(&image));
+        assert!(!protection(&["debian:*"], &[]).is_protected(&image));
+    }
+}
@@ -0,0 +1,203 @@
+//! Image references such as `localhost:5000/team/app:1.2@sha256:...`, split into their parts
+//! the way the registries see them.
+use std::fmt;
+
+/// Where a reference points when it doesn't name a registry.
+pub const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// The namespace of Docker Hub's official images, `ubuntu` is `docker.io/library/ubuntu`.
+const OFFICIAL_NAMESPACE: &str = "library";
+
+/// An image reference, normalised so that `ubuntu`, `library/ubuntu` and
+/// `docker.io/library/ubuntu` all have the same parts.
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct Reference {
+    /// Host and optional port of the registry
+    pub registry: String,
+    /// The path between the registry and the repository, can be empty and can have slashes
+    pub namespace: String,
+    /// The last path component
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl Reference {
+    /// Parses a reference, returning `None` if it isn't valid.
+    pub fn parse(reference: &str) -> Option<Self> {
+        let (rest, digest) = match reference.split_once('@') {
+            Some((rest, digest)) if is_digest(digest) => (rest, Some(digest.to_string())),
+            Some(_) => return None,
+            None => (reference, None),
+        };
+        // A colon after the last slash starts the tag, any before it is the registry's port
+        let (name, tag) = match rest.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, Some(tag.to_string())),
+            _ => (rest, None),
+        };
+        if tag.as_deref().is_some_and(|t| !is_tag(t)) {
+            return None;
+        }
+
+        let mut components = name.split('/').collect::<Vec<_>>();
+        let registry = match components.as_slice() {
+            [first, _, ..] if is_registry(first) => {
+                let registry = components.remove(0);
+                normalise_registry(registry).to_string()
+            }
+            _ => DEFAULT_REGISTRY.to_string(),
+        };
+        if !components.iter().all(|c| is_path_component(c)) {
+            return None;
+        }
+        let repository = components.pop()?.to_string();
+        let mut namespace = components.join("/");
+        if namespace.is_empty() && registry == DEFAULT_REGISTRY {
+            namespace = OFFICIAL_NAMESPACE.to_string();
+        }
+        Some(Self {
+            registry,
+            namespace,
+            repository,
+            tag,
+            digest,
+        })
+    }
+
+    /// The fully qualified repository, without the tag or digest.
+    pub fn name(&self) -> String {
+        if self.namespace.is_empty() {
+            format!("{}/{}", self.registry, self.repository)
+        } else {
+            format!("{}/{}/{}", self.registry, self.namespace, self.repository)
+        }
+    }
+
+    /// The tag, which is `latest` when a reference without a digest leaves it out.
+    pub fn tag_or_latest(&self) -> Option<&str> {
+        match (&self.tag, &self.digest) {
+            (Some(tag), _) => Some(tag),
+            (None, Some(_)) => None,
+            (None, None) => Some("latest"),
+        }
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{tag}")?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{digest}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Docker Hub goes by a few names.
+pub fn normalise_registry(registry: &str) -> &str {
+    match registry {
+        "index.docker.io" | "registry-1.docker.io" => DEFAULT_REGISTRY,
+        registry => registry,
+    }
+}
+
+/// Like docker, the first component is only a registry if it looks like a host name.
+fn is_registry(component: &str) -> bool {
+    component.contains('.')
+        || component.contains(':')
+        || component == "localhost"
+        || component.bytes().any(|c| c.is_ascii_uppercase())
+}
+
+/// Lowercase letters and digits, with single `.` or `_`, double `_` or any number of `-`
+/// between them. This is a little looser than the spec, which is fine for telling the
+/// parts apart.
+fn is_path_component(component: &str) -> bool {
+    let bytes = component.as_bytes();
+    let is_alphanumeric = |c: &u8| c.is_ascii_lowercase() || c.is_ascii_digit();
+    !bytes.is_empty()
+        && bytes.first().is_some_and(is_alphanumeric)
+        && bytes.last().is_some_and(is_alphanumeric)
+        && bytes
+            .iter()
+            .all(|c| is_alphanumeric(c) || b"._-".contains(c))
+}
+
+fn is_tag(tag: &str) -> bool {
+    let bytes = tag.as_bytes();
+    (1..=128).contains(&bytes.len())
+        && bytes[0] != b'.'
+        && bytes[0] != b'-'
+        && bytes
+            .iter()
+            .all(|c| c.is_ascii_alphanumeric() || b"_.-".contains(c))
+}
+
+fn is_digest(digest: &str) -> bool {
+    match digest.split_once(':') {
+        Some((algorithm, hex)) => {
+            !algorithm.is_empty() && hex.len() >= 32 && hex.bytes().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(reference: &str) -> (String, String, String, Option<String>) {
+        let r = Reference::parse(reference).unwrap();
+        (r.registry, r.namespace, r.repository, r.tag)
+    }
+
+    #[test]
+    fn parses_references() {
+        let owned = |r: &str, n: &str, repo: &str, tag: Option<&str>| {
+            (
+                r.to_string(),
+                n.to_string(),
+                repo.to_string(),
+                tag.map(|t| t.to_string()),
+            )
+        };
+        assert_eq!(
+            parts("ubuntu"),
+            owned("docker.io", "library", "ubuntu", None)
+        );
+        assert_eq!(
+            parts("docker.io/library/ubuntu:24.10"),
+            owned("docker.io", "library", "ubuntu", Some("24.10"))
+        );
+        assert_eq!(
+            parts("localhost:5000/app:1.2"),
+            owned("localhost:5000", "", "app", Some("1.2"))
+        );
+        assert_eq!(
+            parts("ghcr.io/org/team/app:pr-12"),
+            owned("ghcr.io", "org/team", "app", Some("pr-12"))
+        );
+        assert_eq!(
+            parts("work/app:1"),
+            owned("docker.io", "work", "app", Some("1"))
+        );
+        assert_eq!(parts("localhost/app"), owned("localhost", "", "app", None));
+
+        let digest = "sha256:cdf755952ed117f6126ff4e65810bf93767d4c38f5c7185b50ec1f1078b464cc";
+        let pinned = Reference::parse(&format!("index.docker.io/ubuntu@{digest}")).unwrap();
+        assert_eq!(pinned.name(), "docker.io/library/ubuntu");
+        assert_eq!(pinned.digest.as_deref(), Some(digest));
+        assert_eq!(pinned.tag_or_latest(), None);
+        let both = Reference::parse(&format!("localhost:5000/app:1.2@{digest}")).unwrap();
+        assert_eq!(both.tag.as_deref(), Some("1.2"));
+        assert_eq!(both.digest.as_deref(), Some(digest));
+
+        assert!(Reference::parse("").is_none());
+        assert!(Reference::parse("Ubuntu").is_none());
+        assert!(Reference::parse("app:").is_none());
+        assert!(Reference::parse("app@latest").is_none());
+    }
+}
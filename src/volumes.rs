@@ -0,0 +1,109 @@
+use crate::args::FilterOptions;
+use crate::backend::Backend;
+use crate::error::Result;
+use crate::output::{Row, VolumeRecord, VolumeSizeSummary};
+use crate::{QueryArgs, Record, docker, engine};
+use jiff::Timestamp;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct Volume {
+    pub name: String,
+    pub driver: String,
+    pub mountpoint: String,
+    pub labels: BTreeMap<String, String>,
+    /// Not every volume driver records when the volume was made
+    pub created_at: Option<Timestamp>,
+    /// Bytes stored in the volume, only the Engine API works this out
+    pub size: Option<u64>,
+    /// Whether any container (running or stopped) mounts it
+    pub in_use: bool,
+}
+
+/// `volume inspect` doesn't say whether the volume is in use, the backend fills that in.
+impl From<docker::Volume> for Volume {
+    fn from(v: docker::Volume) -> Self {
+        Self {
+            name: v.name,
+            driver: v.driver,
+            mountpoint: v.mountpoint,
+            labels: v.labels.unwrap_or_default(),
+            created_at: v.created_at,
+            size: None,
+            in_use: false,
+        }
+    }
+}
+
+impl From<engine::Volume> for Volume {
+    fn from(v: engine::Volume) -> Self {
+        // -1 when the daemon didn't calculate the usage
+        let usage = v.usage_data.unwrap_or_default();
+        Self {
+            name: v.name,
+            driver: v.driver,
+            mountpoint: v.mountpoint,
+            labels: v.labels.unwrap_or_default(),
+            created_at: v.created_at,
+            size: u64::try_from(usage.size).ok(),
+            in_use: usage.ref_count > 0,
+        }
+    }
+}
+
+impl Record for VolumeRecord {
+    type Summary = VolumeSizeSummary;
+
+    fn query(filter: &FilterOptions) -> Result<(String, QueryArgs)> {
+        crate::check_filters(filter)?;
+
+        let mut query_str = "{Volume{".to_string();
+        query_str.push_str(&crate::created_edges(filter));
+
+        let mut query_args = QueryArgs::new();
+        let size_filter = crate::size_filters(filter, &mut query_args);
+        let name_filter = crate::name_filters(filter, &mut query_args);
+
+        if filter.unused {
+            query_str.push_str(r#"in_use @filter(op: "=", value: ["$in_use"])"#);
+            query_str.push('\n');
+            query_args.insert("in_use".into(), false.into());
+        }
+
+        query_str.push_str(&crate::label_filters(filter, &mut query_args));
+
+        query_str.push_str(&format!(
+            "name @output\n{name_filter}driver @output\nmountpoint @output\ncreated @output\nsize @output\n{size_filter}in_use @output\nlabels @output\n"
+        ));
+
+        query_str.push_str("}}");
+        Ok((query_str, query_args))
+    }
+
+    fn from_row(row: Row) -> Self {
+        Self {
+            name: row["name"].as_str().unwrap().to_string(),
+            driver: row["driver"].as_str().unwrap().to_string(),
+            mountpoint: row["mountpoint"].as_str().unwrap().to_string(),
+            created: row["created"].as_str().map(|s| s.to_string()),
+            size: row["size"].as_u64(),
+            in_use: row["in_use"].as_bool().unwrap(),
+            labels: row["labels"]
+                .as_vec_with(|v| v.as_str().map(|s| s.to_string()))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Nothing is shared between volumes, so removing them frees their total size.
+    fn summarise(volumes: &[Self]) -> VolumeSizeSummary {
+        VolumeSizeSummary {
+            volumes: volumes.len(),
+            size: volumes.iter().filter_map(|v| v.size).sum(),
+            unknown_size: volumes.iter().filter(|v| v.size.is_none()).count(),
+        }
+    }
+
+    fn remove(backend: &dyn Backend, names: &[&str]) -> Vec<Result<()>> {
+        backend.remove_volumes(names)
+    }
+}